@@ -0,0 +1,280 @@
+use crate::editor::GameMode;
+use crate::scenario::Scenario;
+use crate::stats::{RestartSession, SessionStats};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How often the controller measures the player and adjusts the difficulty.
+const UPDATE_SECS: f32 = 2.0;
+/// Bound on the integral term, so a long stretch off the goal does not wind it up.
+const INTEGRAL_LIMIT: f32 = 5.0;
+/// Weight of the newest error in the smoothed error the derivative term follows. Accuracy over a
+/// couple of seconds is noisy, and differentiating it raw would jolt the level.
+const ERROR_SMOOTHING: f32 = 0.3;
+
+/// Tunes the difficulty of scenarios that have `adaptive` set, holding the player at its goal.
+///
+/// Every `UPDATE_SECS` the player's accuracy or kill rate over that time is compared with the
+/// goal, and a PID controller sets the difficulty level from the relative error. Targets spawn
+/// smaller at higher levels, expire sooner and strafe faster. The level at each update is kept
+/// in `Difficulty::curve` and saved with the session.
+pub struct AdaptiveDifficultyPlugin;
+
+impl Plugin for AdaptiveDifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Difficulty>();
+        app.add_systems(
+            Update,
+            (
+                restart_difficulty,
+                adapt_difficulty.run_if(in_state(GameMode::Playing)),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Settings of a scenario's adaptive difficulty.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct AdaptiveDifficulty {
+    pub goal: DifficultyGoal,
+    /// Gains of the controller, in difficulty levels per unit of relative error.
+    pub proportional: f32,
+    pub integral: f32,
+    pub derivative: f32,
+    pub min_level: f32,
+    pub max_level: f32,
+}
+
+/// What the controller holds the player at.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum DifficultyGoal {
+    /// Fraction of shots that hit, from 0 to 1.
+    Accuracy(f32),
+    KillsPerMinute(f32),
+}
+
+impl AdaptiveDifficulty {
+    pub fn new(goal: DifficultyGoal) -> Self {
+        AdaptiveDifficulty {
+            goal,
+            proportional: 0.8,
+            integral: 0.15,
+            derivative: 0.1,
+            min_level: 0.5,
+            max_level: 2.5,
+        }
+    }
+}
+
+impl DifficultyGoal {
+    /// Parses `accuracy=<fraction>` or `kpm=<kills per minute>`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (kind, value) = text.split_once('=')?;
+        let value: f32 = value.parse().ok().filter(|value: &f32| *value > 0.0)?;
+        match kind {
+            "accuracy" if value <= 1.0 => Some(DifficultyGoal::Accuracy(value)),
+            "kpm" => Some(DifficultyGoal::KillsPerMinute(value)),
+            _ => None,
+        }
+    }
+}
+
+/// One update of the controller.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct DifficultyPoint {
+    pub elapsed_secs: f32,
+    /// The accuracy or kill rate measured since the previous update.
+    pub measured: f32,
+    pub level: f32,
+}
+
+/// The current difficulty level and the controller's state. The level stays at 1 unless the
+/// scenario is adaptive.
+#[derive(Resource, Debug)]
+pub struct Difficulty {
+    pub level: f32,
+    pub curve: Vec<DifficultyPoint>,
+    timer: Timer,
+    integral: f32,
+    /// Exponential moving average of the error.
+    smoothed_error: Option<f32>,
+    shots_fired: u32,
+    shots_hit: u32,
+    targets_killed: u32,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty {
+            level: 1.0,
+            curve: Vec::new(),
+            timer: Timer::from_seconds(UPDATE_SECS, TimerMode::Repeating),
+            integral: 0.0,
+            smoothed_error: None,
+            shots_fired: 0,
+            shots_hit: 0,
+            targets_killed: 0,
+        }
+    }
+}
+
+impl Difficulty {
+    /// Scale applied to the radius of new targets.
+    pub fn size_scale(&self) -> f32 {
+        1.0 / self.level
+    }
+
+    /// Scale applied to how long targets live.
+    pub fn lifetime_scale(&self) -> f32 {
+        1.0 / self.level
+    }
+
+    /// Scale applied to how fast targets move.
+    pub fn speed_scale(&self) -> f32 {
+        self.level
+    }
+
+    /// Measures the player since the last update, or `None` if there is nothing to measure.
+    fn measure(&mut self, goal: DifficultyGoal, stats: &SessionStats, secs: f32) -> Option<f32> {
+        let shots_fired = stats.shots_fired.saturating_sub(self.shots_fired);
+        let shots_hit = stats.shots_hit.saturating_sub(self.shots_hit);
+        let targets_killed = stats.targets_killed.saturating_sub(self.targets_killed);
+        self.shots_fired = stats.shots_fired;
+        self.shots_hit = stats.shots_hit;
+        self.targets_killed = stats.targets_killed;
+        match goal {
+            DifficultyGoal::Accuracy(_) if shots_fired == 0 => None,
+            DifficultyGoal::Accuracy(_) => Some(shots_hit as f32 / shots_fired as f32),
+            DifficultyGoal::KillsPerMinute(_) => Some(targets_killed as f32 * 60.0 / secs),
+        }
+    }
+
+    /// Runs the controller on one measurement. Doing better than the goal raises the level. The
+    /// derivative term follows the smoothed error, so single noisy measurements barely move it.
+    fn update(&mut self, adaptive: &AdaptiveDifficulty, measured: f32, secs: f32) {
+        let goal = match adaptive.goal {
+            DifficultyGoal::Accuracy(goal) | DifficultyGoal::KillsPerMinute(goal) => goal,
+        };
+        let error = (measured - goal) / goal;
+        self.integral = (self.integral + error * secs).clamp(-INTEGRAL_LIMIT, INTEGRAL_LIMIT);
+        let smoothed = self
+            .smoothed_error
+            .map_or(error, |last| last + ERROR_SMOOTHING * (error - last));
+        let derivative = self
+            .smoothed_error
+            .map_or(0.0, |last| (smoothed - last) / secs);
+        self.smoothed_error = Some(smoothed);
+        let output = adaptive.proportional * error
+            + adaptive.integral * self.integral
+            + adaptive.derivative * derivative;
+        self.level = (1.0 + output).clamp(adaptive.min_level, adaptive.max_level);
+    }
+}
+
+fn restart_difficulty(
+    mut restarts: EventReader<RestartSession>,
+    mut difficulty: ResMut<Difficulty>,
+) {
+    if restarts.read().last().is_some() {
+        *difficulty = Difficulty::default();
+    }
+}
+
+fn adapt_difficulty(
+    mut difficulty: ResMut<Difficulty>,
+    scenario: Res<Scenario>,
+    stats: Res<SessionStats>,
+    time: Res<Time>,
+) {
+    let Some(adaptive) = &scenario.adaptive else {
+        return;
+    };
+    if stats.finished || !difficulty.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Some(measured) = difficulty.measure(adaptive.goal, &stats, UPDATE_SECS) else {
+        return;
+    };
+    difficulty.update(adaptive, measured, UPDATE_SECS);
+    let point = DifficultyPoint {
+        elapsed_secs: stats.elapsed.elapsed_secs(),
+        measured,
+        level: difficulty.level,
+    };
+    difficulty.curve.push(point);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accuracy_goal() -> AdaptiveDifficulty {
+        AdaptiveDifficulty::new(DifficultyGoal::Accuracy(0.5))
+    }
+
+    #[test]
+    fn doing_better_than_the_goal_raises_the_level() {
+        let adaptive = accuracy_goal();
+        let mut better = Difficulty::default();
+        better.update(&adaptive, 0.6, UPDATE_SECS);
+        assert!(better.level > 1.0);
+
+        let mut worse = Difficulty::default();
+        worse.update(&adaptive, 0.4, UPDATE_SECS);
+        assert!(worse.level < 1.0);
+
+        let mut on_goal = Difficulty::default();
+        on_goal.update(&adaptive, 0.5, UPDATE_SECS);
+        assert_eq!(on_goal.level, 1.0);
+    }
+
+    #[test]
+    fn level_stays_within_its_bounds() {
+        let adaptive = accuracy_goal();
+        let mut difficulty = Difficulty::default();
+        for _ in 0..5 {
+            difficulty.update(&adaptive, 1.0, UPDATE_SECS);
+        }
+        assert_eq!(difficulty.level, adaptive.max_level);
+
+        let mut difficulty = Difficulty::default();
+        for _ in 0..5 {
+            difficulty.update(&adaptive, 0.0, UPDATE_SECS);
+        }
+        assert_eq!(difficulty.level, adaptive.min_level);
+    }
+
+    #[test]
+    fn integral_does_not_wind_up() {
+        let adaptive = accuracy_goal();
+        let mut difficulty = Difficulty::default();
+        for _ in 0..100 {
+            difficulty.update(&adaptive, 1.0, UPDATE_SECS);
+        }
+        assert_eq!(difficulty.integral, INTEGRAL_LIMIT);
+
+        // Unbounded, the integral would hold the level up for a couple of hundred more updates.
+        let mut updates = 0;
+        while difficulty.level >= 1.0 {
+            difficulty.update(&adaptive, 0.25, UPDATE_SECS);
+            updates += 1;
+            assert!(updates < 10, "level still {}", difficulty.level);
+        }
+    }
+
+    #[test]
+    fn derivative_follows_the_smoothed_error() {
+        let adaptive = AdaptiveDifficulty {
+            proportional: 0.0,
+            integral: 0.0,
+            ..accuracy_goal()
+        };
+        let mut difficulty = Difficulty::default();
+        difficulty.update(&adaptive, 0.5, UPDATE_SECS);
+        // A jump in error of 1 over one update, of which only part reaches the derivative.
+        difficulty.update(&adaptive, 1.0, UPDATE_SECS);
+        let expected = 1.0 + adaptive.derivative * ERROR_SMOOTHING / UPDATE_SECS;
+        assert!((difficulty.level - expected).abs() < 1e-6);
+    }
+}
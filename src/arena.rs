@@ -0,0 +1,317 @@
+use crate::scenario::Scenario;
+use crate::{SPAWN_PITCH, SPAWN_POINT, SPAWN_YAW};
+use bevy::prelude::*;
+use bevy::scene::SceneInstanceReady;
+use bevy::transform::TransformSystem;
+use bevy_fps_controller::controller::{FpsControllerInput, LogicalPlayer};
+use bevy_rapier3d::prelude::*;
+use rand::distr::Uniform;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Name of the empty marking where the player starts. Its -Z axis is the facing direction.
+const PLAYER_SPAWN_NODE: &str = "PlayerSpawn";
+/// Prefix of the empties marking target spawn volumes, e.g. "TargetSpawn.001".
+/// A unit cube empty scaled to size gives the volume's extents.
+const TARGET_SPAWN_NODE_PREFIX: &str = "TargetSpawn";
+
+pub struct ArenaPlugin;
+
+impl Plugin for ArenaPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ArenaLayout::default());
+        app.add_systems(Update, (spawn_arena, move_player_to_spawn).chain());
+        app.add_systems(
+            PostUpdate,
+            collect_arena_markers.after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Which arena a scenario is played in.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ArenaSource {
+    /// The flat ground and single wall built into the game.
+    #[default]
+    Builtin,
+    /// A glTF file under `assets/`, with colliders generated from its meshes.
+    Gltf {
+        path: String,
+        collider: ArenaCollider,
+    },
+    /// The flat ground with walls and target spawn volumes laid out in the scenario editor.
+    Custom {
+        walls: Vec<Wall>,
+        spawn_volumes: Vec<SpawnVolume>,
+    },
+}
+
+/// How colliders are generated from arena meshes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ArenaCollider {
+    /// Exact triangle meshes. Best for static level geometry.
+    #[default]
+    TriMesh,
+    /// Approximate convex pieces, for meshes that are not closed or are very dense.
+    ConvexDecomposition,
+}
+
+/// A solid box standing in the arena.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Wall {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+    pub rotation: Quat,
+}
+
+/// A box in which targets may be spawned.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpawnVolume {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+    pub rotation: Quat,
+}
+
+impl SpawnVolume {
+    pub fn sample(&self, rng: &mut impl Rng) -> Vec3 {
+        let unit = Uniform::new_inclusive(-1.0f32, 1.0).unwrap();
+        let local = Vec3::new(rng.sample(unit), rng.sample(unit), rng.sample(unit));
+        self.center + self.rotation * (local * self.half_extents)
+    }
+
+    pub fn volume(&self) -> f32 {
+        let size = self.half_extents * 2.0;
+        size.x * size.y * size.z
+    }
+}
+
+/// Spawn points read from the current arena.
+#[derive(Resource, Default, Debug)]
+pub struct ArenaLayout {
+    /// The arena built, which is rebuilt whenever the scenario's arena changes.
+    pub source: Option<ArenaSource>,
+    /// Set once the arena's geometry and markers are in place.
+    pub ready: bool,
+    pub player_spawn: Option<Transform>,
+    pub target_volumes: Vec<SpawnVolume>,
+    awaiting_markers: bool,
+}
+
+impl ArenaLayout {
+    /// Where the player's eye starts out, and which way it faces.
+    pub fn player_view(&self) -> Transform {
+        match self.player_spawn {
+            Some(spawn) => {
+                let yaw = spawn.rotation.to_euler(EulerRot::YXZ).0;
+                Transform::from_translation(spawn.translation).with_rotation(Quat::from_euler(
+                    EulerRot::YXZ,
+                    yaw,
+                    0.0,
+                    0.0,
+                ))
+            }
+            None => Transform::from_translation(SPAWN_POINT).with_rotation(Quat::from_euler(
+                EulerRot::YXZ,
+                SPAWN_YAW,
+                SPAWN_PITCH,
+                0.0,
+            )),
+        }
+    }
+
+    pub fn player_spawn_point(&self) -> Vec3 {
+        self.player_spawn
+            .map_or(SPAWN_POINT, |spawn| spawn.translation)
+    }
+}
+
+/// Part of the arena, removed when it is rebuilt.
+#[derive(Component)]
+struct ArenaGeometry;
+
+#[derive(Component)]
+struct PlayerSpawnMarker;
+
+#[derive(Component)]
+struct TargetSpawnMarker;
+
+/// The walls of the built-in arena, standing on its ground.
+pub fn builtin_walls() -> Vec<Wall> {
+    vec![Wall {
+        center: Vec3::new(0.0, 0.0, 10.0),
+        half_extents: Vec3::new(5.0, 2.5, 0.5),
+        rotation: Quat::IDENTITY,
+    }]
+}
+
+fn spawn_arena(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut layout: ResMut<ArenaLayout>,
+    geometry: Query<Entity, With<ArenaGeometry>>,
+    asset_server: Res<AssetServer>,
+    scenario: Res<Scenario>,
+) {
+    if layout.source.as_ref() == Some(&scenario.arena) {
+        return;
+    }
+    for entity in &geometry {
+        commands.entity(entity).despawn_recursive();
+    }
+    *layout = ArenaLayout {
+        source: Some(scenario.arena.clone()),
+        ..default()
+    };
+
+    match &scenario.arena {
+        ArenaSource::Builtin => {
+            let material = spawn_ground(&mut commands, &mut meshes, &mut materials);
+            for wall in builtin_walls() {
+                spawn_wall(&mut commands, &mut meshes, material.clone(), &wall);
+            }
+            layout.ready = true;
+        }
+        ArenaSource::Custom {
+            walls,
+            spawn_volumes,
+        } => {
+            let material = spawn_ground(&mut commands, &mut meshes, &mut materials);
+            for wall in walls {
+                spawn_wall(&mut commands, &mut meshes, material.clone(), wall);
+            }
+            layout.target_volumes = spawn_volumes.clone();
+            layout.ready = true;
+        }
+        ArenaSource::Gltf { path, collider } => {
+            let shape = match collider {
+                ArenaCollider::TriMesh => {
+                    ComputedColliderShape::TriMesh(TriMeshFlags::MERGE_DUPLICATE_VERTICES)
+                }
+                ArenaCollider::ConvexDecomposition => {
+                    ComputedColliderShape::ConvexDecomposition(VHACDParameters::default())
+                }
+            };
+            let scene = asset_server.load(GltfAssetLabel::Scene(0).from_asset(path.clone()));
+            commands
+                .spawn((
+                    ArenaGeometry,
+                    SceneRoot(scene),
+                    Transform::default(),
+                    AsyncSceneCollider {
+                        shape: Some(shape),
+                        ..default()
+                    },
+                ))
+                .observe(on_arena_scene_loaded);
+        }
+    }
+}
+
+/// Spawns the flat ground, returning its material for walls to share.
+fn spawn_ground(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+) -> Handle<StandardMaterial> {
+    let ground_material = materials.add(StandardMaterial {
+        base_color: Color::srgb(0.5, 0.5, 0.5),
+        ..Default::default()
+    });
+    let ground = Wall {
+        center: Vec3::new(0.0, -0.5, 0.0),
+        half_extents: Vec3::new(20.0, 0.1, 20.0),
+        rotation: Quat::IDENTITY,
+    };
+    spawn_wall(commands, meshes, ground_material.clone(), &ground);
+    ground_material
+}
+
+/// Spawns a box with a matching collider.
+pub fn spawn_wall(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    material: Handle<StandardMaterial>,
+    wall: &Wall,
+) -> Entity {
+    let half = wall.half_extents;
+    commands
+        .spawn((
+            ArenaGeometry,
+            Collider::cuboid(half.x, half.y, half.z),
+            RigidBody::Fixed,
+            Transform::from_translation(wall.center).with_rotation(wall.rotation),
+            Mesh3d(meshes.add(Cuboid::from_size(half * 2.0))),
+            MeshMaterial3d(material),
+        ))
+        .id()
+}
+
+fn on_arena_scene_loaded(
+    trigger: Trigger<SceneInstanceReady>,
+    mut commands: Commands,
+    children_query: Query<&Children>,
+    name_query: Query<&Name>,
+    mut layout: ResMut<ArenaLayout>,
+) {
+    for child in children_query.iter_descendants(trigger.entity()) {
+        let Ok(name) = name_query.get(child) else {
+            continue;
+        };
+        if name.as_str() == PLAYER_SPAWN_NODE {
+            commands.entity(child).insert(PlayerSpawnMarker);
+        } else if name.as_str().starts_with(TARGET_SPAWN_NODE_PREFIX) {
+            commands.entity(child).insert(TargetSpawnMarker);
+        }
+    }
+    // Marker transforms are only final after the next transform propagation.
+    layout.awaiting_markers = true;
+}
+
+fn collect_arena_markers(
+    mut layout: ResMut<ArenaLayout>,
+    player_spawns: Query<&GlobalTransform, With<PlayerSpawnMarker>>,
+    target_spawns: Query<&GlobalTransform, With<TargetSpawnMarker>>,
+) {
+    if !layout.awaiting_markers {
+        return;
+    }
+    layout.awaiting_markers = false;
+    layout.player_spawn = player_spawns
+        .iter()
+        .next()
+        .map(|global| global.compute_transform());
+    layout.target_volumes = target_spawns
+        .iter()
+        .map(|global| {
+            let transform = global.compute_transform();
+            SpawnVolume {
+                center: transform.translation,
+                half_extents: transform.scale.abs(),
+                rotation: transform.rotation,
+            }
+        })
+        .collect();
+    layout.ready = true;
+}
+
+fn move_player_to_spawn(
+    layout: Res<ArenaLayout>,
+    mut player: Query<
+        (&mut Transform, &mut Velocity, &mut FpsControllerInput),
+        With<LogicalPlayer>,
+    >,
+) {
+    if !layout.is_changed() {
+        return;
+    }
+    let Some(spawn) = layout.player_spawn else {
+        return;
+    };
+    for (mut transform, mut velocity, mut input) in &mut player {
+        transform.translation = spawn.translation;
+        velocity.linvel = Vec3::ZERO;
+        input.yaw = spawn.rotation.to_euler(EulerRot::YXZ).0;
+        input.pitch = 0.0;
+    }
+}
@@ -0,0 +1,280 @@
+use bevy::audio::{SpatialScale, Volume};
+use bevy::core::FrameCount;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AudioVolumes::default());
+        app.init_resource::<StartedSounds>();
+        app.add_systems(PreStartup, load_sounds);
+        app.add_systems(Update, apply_volume_changes);
+    }
+}
+
+/// Mixer channel a sound effect is played on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AudioBus {
+    Weapon,
+    Impact,
+    Ui,
+    Feedback,
+}
+
+/// User volume for each bus. Every bus is also scaled by `master`. Set from the player's audio
+/// settings file, see `HotReloadPlugin`.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioVolumes {
+    pub master: f32,
+    pub weapon: f32,
+    pub impact: f32,
+    pub ui: f32,
+    pub feedback: f32,
+}
+
+impl Default for AudioVolumes {
+    fn default() -> Self {
+        AudioVolumes {
+            master: 1.0,
+            weapon: 1.0,
+            impact: 1.0,
+            ui: 1.0,
+            feedback: 1.0,
+        }
+    }
+}
+
+impl AudioVolumes {
+    /// Effective volume of `bus`, including the master volume.
+    pub fn bus(&self, bus: AudioBus) -> f32 {
+        self.master
+            * match bus {
+                AudioBus::Weapon => self.weapon,
+                AudioBus::Impact => self.impact,
+                AudioBus::Ui => self.ui,
+                AudioBus::Feedback => self.feedback,
+            }
+    }
+}
+
+/// Every sound the game can play. Handles are loaded once at startup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SoundEffect {
+    RifleFire,
+    Impact,
+    TargetHit,
+    TargetKill,
+    Headshot,
+    ShotMiss,
+    UiClick,
+    TargetSpawn,
+    TargetHum,
+}
+
+impl SoundEffect {
+    fn all() -> Vec<SoundEffect> {
+        vec![
+            SoundEffect::RifleFire,
+            SoundEffect::Impact,
+            SoundEffect::TargetHit,
+            SoundEffect::TargetKill,
+            SoundEffect::Headshot,
+            SoundEffect::ShotMiss,
+            SoundEffect::UiClick,
+            SoundEffect::TargetSpawn,
+            SoundEffect::TargetHum,
+        ]
+    }
+
+    fn path(&self) -> &'static str {
+        match self {
+            SoundEffect::RifleFire => "sounds/weapons-rifle-assault-rifle-fire-01.ogg",
+            SoundEffect::Impact => "sounds/weapons-shield-metal-impact-ring-02.ogg",
+            SoundEffect::TargetHit => "sounds/target-hit.wav",
+            SoundEffect::TargetKill => "sounds/target-kill.wav",
+            SoundEffect::Headshot => "sounds/headshot.wav",
+            SoundEffect::ShotMiss => "sounds/shot-miss.wav",
+            SoundEffect::UiClick => "sounds/ui-click.wav",
+            SoundEffect::TargetSpawn => "sounds/target-spawn.wav",
+            SoundEffect::TargetHum => "sounds/target-hum.wav",
+        }
+    }
+
+    fn bus(&self) -> AudioBus {
+        match self {
+            SoundEffect::RifleFire => AudioBus::Weapon,
+            SoundEffect::Impact => AudioBus::Impact,
+            SoundEffect::TargetHit
+            | SoundEffect::TargetKill
+            | SoundEffect::Headshot
+            | SoundEffect::ShotMiss
+            | SoundEffect::TargetSpawn
+            | SoundEffect::TargetHum => AudioBus::Feedback,
+            SoundEffect::UiClick => AudioBus::Ui,
+        }
+    }
+
+    /// Further requests for this sound are dropped while this many are playing.
+    fn max_instances(&self) -> usize {
+        match self {
+            SoundEffect::RifleFire => 8,
+            SoundEffect::Impact => 6,
+            SoundEffect::TargetHum => 8,
+            _ => 3,
+        }
+    }
+
+    /// Distance scale for spatial playback; smaller values make the sound carry further.
+    fn spatial_scale(&self) -> f32 {
+        match self {
+            SoundEffect::Impact => 0.2,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct SoundHandles(HashMap<SoundEffect, Handle<AudioSource>>);
+
+/// Attached to every sound started through `SoundPlayer`.
+#[derive(Component)]
+pub struct PlayingSound {
+    effect: SoundEffect,
+    /// Volume before the bus volume is applied.
+    volume: f32,
+    /// `FrameCount` when the sound was started.
+    frame: u32,
+}
+
+/// Sounds started during the current frame. Their entities are only spawned when commands are
+/// applied, so they are counted here until then.
+#[derive(Resource, Default)]
+struct StartedSounds {
+    frame: u32,
+    counts: HashMap<SoundEffect, usize>,
+}
+
+/// Plays preloaded sound effects through their volume bus.
+#[derive(SystemParam)]
+pub struct SoundPlayer<'w, 's> {
+    handles: Res<'w, SoundHandles>,
+    volumes: Res<'w, AudioVolumes>,
+    playing: Query<'w, 's, &'static PlayingSound>,
+    started: ResMut<'w, StartedSounds>,
+    frame: Res<'w, FrameCount>,
+}
+
+impl SoundPlayer<'_, '_> {
+    /// Plays `effect` once, at `position` if given, otherwise without spatialisation.
+    pub fn play(
+        &mut self,
+        commands: &mut Commands,
+        effect: SoundEffect,
+        volume: f32,
+        speed: f32,
+        position: Option<Vec3>,
+    ) {
+        if !self.start(effect) {
+            return;
+        }
+
+        let settings = PlaybackSettings::DESPAWN
+            .with_volume(Volume::new(volume * self.volumes.bus(effect.bus())))
+            .with_speed(speed);
+        let mut sound = commands.spawn((
+            AudioPlayer::new(self.handles.0[&effect].clone()),
+            PlayingSound {
+                effect,
+                volume,
+                frame: self.frame.0,
+            },
+        ));
+        match position {
+            Some(position) => sound.insert((
+                settings
+                    .with_spatial(true)
+                    .with_spatial_scale(SpatialScale::new(effect.spatial_scale())),
+                Transform::from_translation(position),
+            )),
+            None => sound.insert(settings),
+        };
+    }
+
+    /// Loops `effect` from `entity`, following it around until it is despawned.
+    pub fn play_looping_on(
+        &mut self,
+        commands: &mut Commands,
+        entity: Entity,
+        effect: SoundEffect,
+        volume: f32,
+    ) {
+        if !self.start(effect) {
+            return;
+        }
+
+        // The entity may be despawned before the command is applied, when a target is killed
+        // or expires in the frame it appears.
+        commands.entity(entity).try_insert((
+            AudioPlayer::new(self.handles.0[&effect].clone()),
+            PlaybackSettings::LOOP
+                .with_volume(Volume::new(volume * self.volumes.bus(effect.bus())))
+                .with_spatial(true)
+                .with_spatial_scale(SpatialScale::new(effect.spatial_scale())),
+            PlayingSound {
+                effect,
+                volume,
+                frame: self.frame.0,
+            },
+        ));
+    }
+
+    /// Counts another instance of `effect`, or returns false if there are already as many as it
+    /// may have. Sounds started this frame count whether or not they have been spawned yet.
+    fn start(&mut self, effect: SoundEffect) -> bool {
+        let frame = self.frame.0;
+        if self.started.frame != frame {
+            self.started.frame = frame;
+            self.started.counts.clear();
+        }
+        let earlier = self
+            .playing
+            .iter()
+            .filter(|playing| playing.effect == effect && playing.frame != frame)
+            .count();
+        let this_frame = self.started.counts.entry(effect).or_default();
+        if earlier + *this_frame >= effect.max_instances() {
+            return false;
+        }
+        *this_frame += 1;
+        true
+    }
+}
+
+fn load_sounds(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handles = SoundEffect::all()
+        .into_iter()
+        .map(|effect| (effect, asset_server.load(effect.path())))
+        .collect();
+    commands.insert_resource(SoundHandles(handles));
+}
+
+fn apply_volume_changes(
+    volumes: Res<AudioVolumes>,
+    sinks: Query<(&PlayingSound, &AudioSink)>,
+    spatial_sinks: Query<(&PlayingSound, &SpatialAudioSink)>,
+) {
+    if !volumes.is_changed() {
+        return;
+    }
+    for (playing, sink) in &sinks {
+        sink.set_volume(playing.volume * volumes.bus(playing.effect.bus()));
+    }
+    for (playing, sink) in &spatial_sinks {
+        sink.set_volume(playing.volume * volumes.bus(playing.effect.bus()));
+    }
+}
@@ -0,0 +1,211 @@
+use crate::scenario::Scenario;
+use crate::ShotFired;
+use bevy::prelude::*;
+use bevy::time::Stopwatch;
+use bevy_fps_controller::controller::FpsController;
+
+/// Shots fired longer than this after a strafe ends are not counted as counter-strafes.
+const DRILL_WINDOW_SECS: f32 = 1.0;
+/// Pressing the opposite key for at most this long is a counter-strafe tap rather than a new strafe.
+const COUNTER_TAP_SECS: f32 = 0.25;
+
+pub struct CounterStrafePlugin;
+
+impl Plugin for CounterStrafePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(StrafeTracker::default());
+        app.insert_resource(CounterStrafeStats::default());
+        app.add_systems(Startup, setup);
+        app.add_systems(
+            Update,
+            (track_strafe_keys, record_counter_strafes, update_feedback).chain(),
+        );
+    }
+}
+
+/// One shot fired shortly after the end of a strafe.
+#[derive(Clone, Debug)]
+pub struct CounterStrafeSample {
+    /// Time from releasing the strafe key to firing.
+    pub release_to_shot_secs: f32,
+    /// Horizontal speed left when the shot went off.
+    pub speed: f32,
+    /// The opposite key was tapped to stop, rather than just letting go.
+    pub countered: bool,
+    /// The player had slowed down enough for the shot to be fully accurate.
+    pub accurate: bool,
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct CounterStrafeStats {
+    pub samples: Vec<CounterStrafeSample>,
+}
+
+impl CounterStrafeStats {
+    /// Fraction of counter-strafe shots that were fully accurate.
+    pub fn accurate_fraction(&self) -> f32 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        let accurate = self.samples.iter().filter(|sample| sample.accurate).count();
+        accurate as f32 / self.samples.len() as f32
+    }
+
+    pub fn average_release_to_shot_secs(&self) -> f32 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        let total: f32 = self
+            .samples
+            .iter()
+            .map(|sample| sample.release_to_shot_secs)
+            .sum();
+        total / self.samples.len() as f32
+    }
+}
+
+/// Where the player is in a strafe, counter-strafe, shoot sequence.
+#[derive(Resource, Default)]
+struct StrafeTracker {
+    /// The strafe key most recently released, ending a strafe.
+    released: Option<KeyCode>,
+    /// Running from the end of the strafe until a shot is fired.
+    since_release: Option<Stopwatch>,
+    /// How long the opposite key has been held, while it may still be a counter-strafe tap.
+    counter_hold: Option<Stopwatch>,
+    countered: bool,
+}
+
+#[derive(Component)]
+struct CounterStrafeText;
+
+fn setup(mut commands: Commands, scenario: Res<Scenario>) {
+    if !scenario.counter_strafe_drill {
+        return;
+    }
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(60.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        })
+        .with_child((Text::new(""), CounterStrafeText));
+}
+
+fn track_strafe_keys(
+    key: Res<ButtonInput<KeyCode>>,
+    controllers: Query<&FpsController>,
+    mut tracker: ResMut<StrafeTracker>,
+    time: Res<Time>,
+) {
+    let Ok(controller) = controllers.get_single() else {
+        return;
+    };
+    let strafe_keys = [controller.key_left, controller.key_right];
+    let tracker = &mut *tracker;
+
+    if let Some(since_release) = tracker.since_release.as_mut() {
+        since_release.tick(time.delta());
+    }
+    if let Some(counter_hold) = tracker.counter_hold.as_mut() {
+        counter_hold.tick(time.delta());
+        // Held too long to be a tap, so this is a strafe the other way.
+        if counter_hold.elapsed_secs() > COUNTER_TAP_SECS {
+            tracker.counter_hold = None;
+            tracker.since_release = None;
+            tracker.released = None;
+        }
+    }
+
+    for pressed in strafe_keys {
+        if !key.just_pressed(pressed) {
+            continue;
+        }
+        match tracker.released {
+            Some(released) if released != pressed && tracker.since_release.is_some() => {
+                tracker.counter_hold = Some(Stopwatch::new());
+                tracker.countered = true;
+            }
+            _ => {
+                tracker.since_release = None;
+                tracker.released = None;
+            }
+        }
+    }
+
+    let strafing = strafe_keys
+        .iter()
+        .any(|strafe_key| key.pressed(*strafe_key));
+    for released in strafe_keys {
+        if !key.just_released(released) || strafing {
+            continue;
+        }
+        if tracker.counter_hold.take().is_some() {
+            // The end of the tap. The strafe already ended when the first key was let go.
+            continue;
+        }
+        tracker.released = Some(released);
+        tracker.since_release = Some(Stopwatch::new());
+        tracker.countered = false;
+    }
+}
+
+fn record_counter_strafes(
+    mut shots_fired: EventReader<ShotFired>,
+    mut tracker: ResMut<StrafeTracker>,
+    mut stats: ResMut<CounterStrafeStats>,
+    scenario: Res<Scenario>,
+) {
+    for shot in shots_fired.read() {
+        if !scenario.counter_strafe_drill {
+            continue;
+        }
+        let Some(since_release) = tracker.since_release.take() else {
+            continue;
+        };
+        if since_release.elapsed_secs() > DRILL_WINDOW_SECS {
+            continue;
+        }
+        let sample = CounterStrafeSample {
+            release_to_shot_secs: since_release.elapsed_secs(),
+            speed: shot.speed,
+            countered: tracker.countered,
+            accurate: shot.accurate,
+        };
+        stats.samples.push(sample);
+    }
+}
+
+fn update_feedback(
+    stats: Res<CounterStrafeStats>,
+    mut texts: Query<(&mut Text, &mut TextColor), With<CounterStrafeText>>,
+) {
+    if !stats.is_changed() {
+        return;
+    }
+    let Some(last) = stats.samples.last() else {
+        return;
+    };
+    let label = if last.countered {
+        "Counter-strafe"
+    } else {
+        "Stop"
+    };
+    for (mut text, mut color) in &mut texts {
+        text.0 = format!(
+            "{} {:.0} ms, {:.1} m/s  |  Accurate: {:.0}%, average {:.0} ms",
+            label,
+            last.release_to_shot_secs * 1000.0,
+            last.speed,
+            stats.accurate_fraction() * 100.0,
+            stats.average_release_to_shot_secs() * 1000.0,
+        );
+        color.0 = if last.accurate {
+            Color::srgb(0.3, 1.0, 0.3)
+        } else {
+            Color::srgb(1.0, 0.3, 0.3)
+        };
+    }
+}
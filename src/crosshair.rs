@@ -0,0 +1,66 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Draws the crosshair dot in the middle of the screen, redrawing it whenever the `Crosshair`
+/// settings change.
+pub struct CrosshairPlugin;
+
+impl Plugin for CrosshairPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Crosshair>();
+        app.add_systems(Startup, spawn_crosshair);
+        app.add_systems(Update, update_crosshair);
+    }
+}
+
+/// How the crosshair looks.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct Crosshair {
+    /// Radius of the dot, in pixels.
+    pub radius: f32,
+    pub color: Color,
+}
+
+impl Default for Crosshair {
+    fn default() -> Self {
+        Crosshair {
+            radius: 2.0,
+            color: Color::srgb(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+#[derive(Component)]
+struct CrosshairDot;
+
+fn spawn_crosshair(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials2d: ResMut<Assets<ColorMaterial>>,
+    crosshair: Res<Crosshair>,
+) {
+    commands.spawn((
+        CrosshairDot,
+        Mesh2d(meshes.add(Circle::new(crosshair.radius))),
+        MeshMaterial2d(materials2d.add(crosshair.color)),
+        Transform::from_xyz(0.0, 0.0, 0.0),
+    ));
+}
+
+fn update_crosshair(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials2d: ResMut<Assets<ColorMaterial>>,
+    crosshair: Res<Crosshair>,
+    dots: Query<Entity, With<CrosshairDot>>,
+) {
+    if !crosshair.is_changed() || crosshair.is_added() {
+        return;
+    }
+    for dot in &dots {
+        commands.entity(dot).insert((
+            Mesh2d(meshes.add(Circle::new(crosshair.radius))),
+            MeshMaterial2d(materials2d.add(crosshair.color)),
+        ));
+    }
+}
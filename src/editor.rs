@@ -0,0 +1,533 @@
+use crate::arena::{builtin_walls, ArenaSource, SpawnVolume, Wall};
+use crate::scenario::{Scenario, TargetMotion, TargetPlacement, TargetSize};
+use crate::stats::RestartSession;
+use bevy::prelude::*;
+use bevy::window::CursorGrabMode;
+use bevy_fps_controller::controller::{LogicalPlayer, RenderPlayer};
+use bevy_rapier3d::prelude::*;
+use std::f32::consts::PI;
+use std::path::PathBuf;
+
+/// Edited scenarios are saved here, as `<name>.scenario.ron`, so they can be played with
+/// `aim_trainer scenarios/<name>.scenario.ron`.
+const SCENARIO_DIRECTORY: &str = "assets/scenarios";
+/// How far away items can be placed.
+const PLACE_DISTANCE: f32 = 100.0;
+/// How much one key press grows or shrinks an item along one axis, in metres.
+const SIZE_STEP: f32 = 0.25;
+/// How far one key press turns an item.
+const ROTATION_STEP: f32 = PI / 12.0;
+/// Target size is multiplied or divided by this per key press.
+const TARGET_SIZE_STEP: f32 = 1.1;
+/// How much one key press changes strafing speed, in metres per second.
+const MOTION_SPEED_STEP: f32 = 0.5;
+
+const NEW_WALL_HALF_EXTENTS: Vec3 = Vec3::new(2.0, 1.5, 0.25);
+const NEW_VOLUME_HALF_EXTENTS: Vec3 = Vec3::new(2.0, 1.0, 2.0);
+
+/// Lets the current scenario be laid out from inside the game: walls and target spawn volumes
+/// are placed at the crosshair, and target size and motion adjusted. Changes apply to the
+/// scenario straight away, so leaving the editor plays the edited drill.
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<GameMode>();
+        app.insert_resource(EditorState::default());
+        app.add_systems(Startup, setup);
+        app.add_systems(OnEnter(GameMode::Editing), enter_editor);
+        app.add_systems(OnExit(GameMode::Editing), leave_editor);
+        app.add_systems(
+            Update,
+            (
+                (
+                    place_item,
+                    edit_selected_item,
+                    edit_targets,
+                    save_scenario,
+                    test_play,
+                )
+                    .chain(),
+                draw_editor_gizmos,
+            )
+                .run_if(in_state(GameMode::Editing)),
+        );
+        app.add_systems(Update, update_editor_text);
+    }
+}
+
+/// Whether the player is playing the scenario or laying it out.
+#[derive(States, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GameMode {
+    #[default]
+    Playing,
+    Editing,
+}
+
+/// What a left click places.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum EditorTool {
+    #[default]
+    Wall,
+    SpawnVolume,
+}
+
+/// An item of the custom arena, by index.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EditorItem {
+    Wall(usize),
+    SpawnVolume(usize),
+}
+
+#[derive(Resource, Default)]
+struct EditorState {
+    tool: EditorTool,
+    selected: Option<EditorItem>,
+    /// Result of the last action worth reporting, such as saving.
+    message: String,
+}
+
+#[derive(Component)]
+struct EditorText;
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(5.0),
+            left: Val::Px(15.0),
+            ..default()
+        })
+        .with_child((Text::new(""), EditorText, Visibility::Hidden));
+}
+
+/// Clears the targets while editing. They come back when play resumes.
+fn enter_editor(mut restarts: EventWriter<RestartSession>, mut state: ResMut<EditorState>) {
+    restarts.send(RestartSession);
+    state.message.clear();
+}
+
+/// Starts a fresh session with the edited scenario. Target spawning is paused while editing, so
+/// the restart sent on entering the editor has long been dropped by now.
+fn leave_editor(mut restarts: EventWriter<RestartSession>) {
+    restarts.send(RestartSession);
+}
+
+/// The walls and spawn volumes of the scenario's arena, switching a built-in arena to an
+/// editable copy of it. `None` for glTF arenas, which are edited in a modelling tool.
+fn custom_arena(scenario: &mut Scenario) -> Option<(&mut Vec<Wall>, &mut Vec<SpawnVolume>)> {
+    if matches!(scenario.arena, ArenaSource::Builtin) {
+        scenario.arena = ArenaSource::Custom {
+            walls: builtin_walls(),
+            spawn_volumes: Vec::new(),
+        };
+    }
+    match &mut scenario.arena {
+        ArenaSource::Custom {
+            walls,
+            spawn_volumes,
+        } => Some((walls, spawn_volumes)),
+        _ => None,
+    }
+}
+
+fn place_item(
+    buttons: Res<ButtonInput<MouseButton>>,
+    key: Res<ButtonInput<KeyCode>>,
+    rapier_context: ReadRapierContext,
+    player: Query<Entity, With<LogicalPlayer>>,
+    camera: Query<&Transform, With<RenderPlayer>>,
+    window: Query<&Window>,
+    mut scenario: ResMut<Scenario>,
+    mut state: ResMut<EditorState>,
+    mut was_grabbed: Local<bool>,
+) {
+    if key.just_pressed(KeyCode::KeyT) {
+        state.tool = match state.tool {
+            EditorTool::Wall => EditorTool::SpawnVolume,
+            EditorTool::SpawnVolume => EditorTool::Wall,
+        };
+    }
+
+    // The click that grabs the cursor only grabs it.
+    let grabbed = window
+        .iter()
+        .any(|window| window.cursor_options.grab_mode != CursorGrabMode::None);
+    let clicked = buttons.just_pressed(MouseButton::Left) && *was_grabbed;
+    *was_grabbed = grabbed;
+    let (Ok(player), Ok(camera_transform)) = (player.get_single(), camera.get_single()) else {
+        return;
+    };
+    if !clicked {
+        return;
+    }
+
+    let filter = QueryFilter::new()
+        .exclude_sensors()
+        .exclude_rigid_body(player);
+    let origin = camera_transform.translation;
+    let direction = camera_transform.forward().as_vec3();
+    let Some((_, distance)) =
+        rapier_context
+            .single()
+            .cast_ray(origin, direction, PLACE_DISTANCE, true, filter)
+    else {
+        return;
+    };
+    let hit_point = origin + direction * distance;
+    let yaw = camera_transform.rotation.to_euler(EulerRot::YXZ).0;
+
+    let tool = state.tool;
+    let Some((walls, spawn_volumes)) = custom_arena(&mut scenario) else {
+        state.message = String::from("glTF arenas cannot be edited here");
+        return;
+    };
+    state.selected = Some(match tool {
+        EditorTool::Wall => {
+            // Standing on the surface, facing the player.
+            walls.push(Wall {
+                center: hit_point + Vec3::Y * NEW_WALL_HALF_EXTENTS.y,
+                half_extents: NEW_WALL_HALF_EXTENTS,
+                rotation: Quat::from_rotation_y(yaw),
+            });
+            EditorItem::Wall(walls.len() - 1)
+        }
+        EditorTool::SpawnVolume => {
+            spawn_volumes.push(SpawnVolume {
+                center: hit_point + Vec3::Y * (NEW_VOLUME_HALF_EXTENTS.y + 0.5),
+                half_extents: NEW_VOLUME_HALF_EXTENTS,
+                rotation: Quat::from_rotation_y(yaw),
+            });
+            EditorItem::SpawnVolume(spawn_volumes.len() - 1)
+        }
+    });
+    if tool == EditorTool::SpawnVolume {
+        scenario.target_placement = TargetPlacement::ArenaVolumes;
+    }
+}
+
+fn edit_selected_item(
+    key: Res<ButtonInput<KeyCode>>,
+    mut scenario: ResMut<Scenario>,
+    mut state: ResMut<EditorState>,
+) {
+    let resize = [
+        (KeyCode::ArrowRight, Vec3::X),
+        (KeyCode::ArrowLeft, -Vec3::X),
+        (KeyCode::ArrowUp, Vec3::Y),
+        (KeyCode::ArrowDown, -Vec3::Y),
+        (KeyCode::PageUp, Vec3::Z),
+        (KeyCode::PageDown, -Vec3::Z),
+    ]
+    .into_iter()
+    .filter(|(code, _)| key.just_pressed(*code))
+    .map(|(_, axis)| axis * SIZE_STEP)
+    .sum::<Vec3>();
+    let mut turn = 0.0;
+    if key.just_pressed(KeyCode::KeyQ) {
+        turn += ROTATION_STEP;
+    }
+    if key.just_pressed(KeyCode::KeyE) {
+        turn -= ROTATION_STEP;
+    }
+    let cycle = key.just_pressed(KeyCode::Tab);
+    let delete = key.just_pressed(KeyCode::Delete) || key.just_pressed(KeyCode::Backspace);
+    if resize == Vec3::ZERO && turn == 0.0 && !cycle && !delete {
+        return;
+    }
+
+    let Some((walls, spawn_volumes)) = custom_arena(&mut scenario) else {
+        return;
+    };
+    if cycle {
+        // Walls first, then volumes, wrapping around.
+        let count = walls.len() + spawn_volumes.len();
+        let next = match state.selected {
+            Some(EditorItem::Wall(index)) => index + 1,
+            Some(EditorItem::SpawnVolume(index)) => walls.len() + index + 1,
+            None => 0,
+        };
+        state.selected = (count > 0).then(|| {
+            let next = next % count;
+            if next < walls.len() {
+                EditorItem::Wall(next)
+            } else {
+                EditorItem::SpawnVolume(next - walls.len())
+            }
+        });
+    }
+
+    let Some(selected) = state.selected else {
+        return;
+    };
+    let (center, half_extents, rotation) = match selected {
+        EditorItem::Wall(index) if index < walls.len() => {
+            if delete {
+                walls.remove(index);
+                state.selected = None;
+                return;
+            }
+            let wall = &mut walls[index];
+            (&mut wall.center, &mut wall.half_extents, &mut wall.rotation)
+        }
+        EditorItem::SpawnVolume(index) if index < spawn_volumes.len() => {
+            if delete {
+                spawn_volumes.remove(index);
+                state.selected = None;
+                return;
+            }
+            let volume = &mut spawn_volumes[index];
+            (
+                &mut volume.center,
+                &mut volume.half_extents,
+                &mut volume.rotation,
+            )
+        }
+        _ => {
+            state.selected = None;
+            return;
+        }
+    };
+    let new_half_extents = (*half_extents + resize / 2.0).max(Vec3::splat(SIZE_STEP / 2.0));
+    // Keep the bottom where it is, so walls stay on the ground as they grow.
+    center.y += new_half_extents.y - half_extents.y;
+    *half_extents = new_half_extents;
+    *rotation = Quat::from_rotation_y(turn) * *rotation;
+}
+
+fn edit_targets(key: Res<ButtonInput<KeyCode>>, mut scenario: ResMut<Scenario>) {
+    let mut size_factor = 1.0;
+    if key.just_pressed(KeyCode::Equal) {
+        size_factor *= TARGET_SIZE_STEP;
+    }
+    if key.just_pressed(KeyCode::Minus) {
+        size_factor /= TARGET_SIZE_STEP;
+    }
+    if size_factor != 1.0 {
+        match &mut scenario.target_size {
+            TargetSize::Radius { min, max } => {
+                *min *= size_factor;
+                *max *= size_factor;
+            }
+            TargetSize::Angular { min_deg, max_deg } => {
+                *min_deg *= size_factor;
+                *max_deg *= size_factor;
+            }
+        }
+    }
+
+    if key.just_pressed(KeyCode::KeyM) {
+        scenario.target_motion = match scenario.target_motion {
+            TargetMotion::Static => TargetMotion::Strafe {
+                speed: 2.0,
+                distance: 4.0,
+            },
+            TargetMotion::Strafe { .. } => TargetMotion::Static,
+        };
+    }
+    let mut speed_change = 0.0;
+    if key.just_pressed(KeyCode::BracketRight) {
+        speed_change += MOTION_SPEED_STEP;
+    }
+    if key.just_pressed(KeyCode::BracketLeft) {
+        speed_change -= MOTION_SPEED_STEP;
+    }
+    if speed_change != 0.0 {
+        if let TargetMotion::Strafe { speed, .. } = &mut scenario.target_motion {
+            *speed = (*speed + speed_change).max(MOTION_SPEED_STEP);
+        }
+    }
+}
+
+fn save_scenario(
+    key: Res<ButtonInput<KeyCode>>,
+    scenario: Res<Scenario>,
+    mut state: ResMut<EditorState>,
+) {
+    if !key.just_pressed(KeyCode::F6) {
+        return;
+    }
+    let file_name: String = scenario
+        .name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let path = PathBuf::from(SCENARIO_DIRECTORY).join(format!("{}.scenario.ron", file_name));
+    state.message = match scenario.save(&path) {
+        Ok(()) => format!("Saved {}", path.display()),
+        Err(error) => format!("Could not save {}: {}", path.display(), error),
+    };
+    println!("{}", state.message);
+}
+
+fn test_play(key: Res<ButtonInput<KeyCode>>, mut next_mode: ResMut<NextState<GameMode>>) {
+    if key.just_pressed(KeyCode::F5) {
+        next_mode.set(GameMode::Playing);
+    }
+}
+
+/// Spawn volumes have no geometry, so they are drawn as boxes. The selected item is yellow.
+fn draw_editor_gizmos(mut gizmos: Gizmos, scenario: Res<Scenario>, state: Res<EditorState>) {
+    let ArenaSource::Custom {
+        walls,
+        spawn_volumes,
+    } = &scenario.arena
+    else {
+        return;
+    };
+    let selected_color = Color::srgb(1.0, 0.9, 0.2);
+    for (index, volume) in spawn_volumes.iter().enumerate() {
+        let color = if state.selected == Some(EditorItem::SpawnVolume(index)) {
+            selected_color
+        } else {
+            Color::srgb(0.2, 1.0, 0.4)
+        };
+        gizmos.cuboid(
+            Transform::from_translation(volume.center)
+                .with_rotation(volume.rotation)
+                .with_scale(volume.half_extents * 2.0),
+            color,
+        );
+    }
+    if let Some(EditorItem::Wall(index)) = state.selected {
+        if let Some(wall) = walls.get(index) {
+            gizmos.cuboid(
+                Transform::from_translation(wall.center)
+                    .with_rotation(wall.rotation)
+                    .with_scale(wall.half_extents * 2.0 + Vec3::splat(0.05)),
+                selected_color,
+            );
+        }
+    }
+}
+
+fn update_editor_text(
+    mode: Res<State<GameMode>>,
+    scenario: Res<Scenario>,
+    state: Res<EditorState>,
+    mut texts: Query<(&mut Text, &mut Visibility), With<EditorText>>,
+) {
+    let editing = *mode.get() == GameMode::Editing;
+    for (mut text, mut visibility) in &mut texts {
+        visibility.set_if_neq(if editing {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+        if !editing {
+            continue;
+        }
+        let tool = match state.tool {
+            EditorTool::Wall => "wall",
+            EditorTool::SpawnVolume => "spawn volume",
+        };
+        let selected = match state.selected {
+            Some(EditorItem::Wall(index)) => format!("wall {}", index + 1),
+            Some(EditorItem::SpawnVolume(index)) => format!("spawn volume {}", index + 1),
+            None => String::from("nothing"),
+        };
+        let size = match scenario.target_size {
+            TargetSize::Radius { min, max } => format!("{:.2}-{:.2} m", min, max),
+            TargetSize::Angular { min_deg, max_deg } => {
+                format!("{:.1}-{:.1}°", min_deg, max_deg)
+            }
+        };
+        let motion = match scenario.target_motion {
+            TargetMotion::Static => String::from("static"),
+            TargetMotion::Strafe { speed, distance } => {
+                format!("strafing {:.1} m/s over {:.1} m", speed, distance)
+            }
+        };
+        text.0 = format!(
+            "Editing {}\n\
+             Click: place {} (T to switch)  |  Selected: {} (Tab to cycle)\n\
+             Arrows, Page Up/Down: resize  |  Q/E: turn  |  Delete: remove\n\
+             Targets: {} (-/=), {} (M, [/])\n\
+             F5: test play  |  F6: save\n\
+             {}",
+            scenario.name, tool, selected, size, motion, state.message
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptive::Difficulty;
+    use crate::arena::ArenaLayout;
+    use crate::net::{spawns_targets, NetRole};
+    use crate::scenario::GridOccupancy;
+    use crate::{restart_targets, spawn_initial_targets, Target, TargetRng};
+    use bevy::state::app::StatesPlugin;
+
+    /// Just enough of the game to spawn targets and switch in and out of the editor.
+    fn app() -> App {
+        let scenario = Scenario::default();
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin))
+            .init_state::<GameMode>()
+            .add_event::<RestartSession>()
+            .insert_resource(Assets::<Mesh>::default())
+            .insert_resource(Assets::<StandardMaterial>::default())
+            .insert_resource(GridOccupancy::new(scenario.target_placement.grid_cells()))
+            .insert_resource(scenario)
+            .insert_resource(TargetRng::new(1))
+            .insert_resource(ArenaLayout {
+                ready: true,
+                ..default()
+            })
+            .insert_resource(Difficulty::default())
+            .insert_resource(NetRole::Offline)
+            .insert_resource(EditorState::default())
+            .add_systems(OnEnter(GameMode::Editing), enter_editor)
+            .add_systems(OnExit(GameMode::Editing), leave_editor)
+            .add_systems(
+                Update,
+                (
+                    restart_targets,
+                    spawn_initial_targets.run_if(in_state(GameMode::Playing)),
+                )
+                    .chain()
+                    .run_if(spawns_targets),
+            );
+        app
+    }
+
+    fn target_count(app: &mut App) -> usize {
+        app.world_mut()
+            .query_filtered::<(), With<Target>>()
+            .iter(app.world())
+            .count()
+    }
+
+    fn set_mode(app: &mut App, mode: GameMode) {
+        app.world_mut()
+            .resource_mut::<NextState<GameMode>>()
+            .set(mode);
+        app.update();
+    }
+
+    #[test]
+    fn targets_come_back_after_editing() {
+        let mut app = app();
+        app.update();
+        let count = app.world().resource::<Scenario>().target_count;
+        assert_eq!(target_count(&mut app), count);
+
+        set_mode(&mut app, GameMode::Editing);
+        // Long enough for the restart sent on entering the editor to be dropped.
+        for _ in 0..5 {
+            app.update();
+        }
+        assert_eq!(target_count(&mut app), 0);
+
+        set_mode(&mut app, GameMode::Playing);
+        assert_eq!(target_count(&mut app), count);
+    }
+}
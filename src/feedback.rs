@@ -0,0 +1,125 @@
+use crate::audio::{SoundEffect, SoundPlayer};
+use crate::scenario::Scenario;
+use crate::{ShotMissed, Target, TargetHit};
+use bevy::prelude::*;
+use rand::distr::Uniform;
+use rand::prelude::*;
+
+pub struct FeedbackPlugin;
+
+impl Plugin for FeedbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FeedbackSounds::default());
+        app.add_systems(
+            Update,
+            (play_hit_feedback, play_miss_feedback, emit_target_audio),
+        );
+    }
+}
+
+/// A feedback cue: which sound to play, how loud, and how much its pitch may vary.
+#[derive(Clone, Debug)]
+pub struct FeedbackSound {
+    pub effect: SoundEffect,
+    pub volume: f32,
+    /// Playback speed is randomised by up to this much either way.
+    pub pitch_variation: f32,
+}
+
+/// Sounds played in response to the player's shots. `None` silences a cue.
+#[derive(Resource, Clone, Debug)]
+pub struct FeedbackSounds {
+    /// A target was hit but survived.
+    pub hit: Option<FeedbackSound>,
+    pub kill: Option<FeedbackSound>,
+    /// A hit close to the centre of the target, on top of the hit or kill cue.
+    pub headshot: Option<FeedbackSound>,
+    /// A shot that hit no target.
+    pub miss: Option<FeedbackSound>,
+}
+
+impl Default for FeedbackSounds {
+    fn default() -> Self {
+        FeedbackSounds {
+            hit: Some(FeedbackSound::new(SoundEffect::TargetHit, 0.4, 0.05)),
+            kill: Some(FeedbackSound::new(SoundEffect::TargetKill, 0.4, 0.0)),
+            headshot: Some(FeedbackSound::new(SoundEffect::Headshot, 0.45, 0.03)),
+            miss: Some(FeedbackSound::new(SoundEffect::ShotMiss, 0.25, 0.1)),
+        }
+    }
+}
+
+impl FeedbackSound {
+    pub fn new(effect: SoundEffect, volume: f32, pitch_variation: f32) -> Self {
+        FeedbackSound {
+            effect,
+            volume,
+            pitch_variation,
+        }
+    }
+
+    fn play(&self, commands: &mut Commands, sounds: &mut SoundPlayer) {
+        let mut rng = rand::rng();
+        let speed = if self.pitch_variation > 0.0 {
+            let range = Uniform::new(-self.pitch_variation, self.pitch_variation).unwrap();
+            1.0 + rng.sample(range)
+        } else {
+            1.0
+        };
+        sounds.play(commands, self.effect, self.volume, speed, None);
+    }
+}
+
+fn play_hit_feedback(
+    mut commands: Commands,
+    mut target_hits: EventReader<TargetHit>,
+    mut sounds: SoundPlayer,
+    cues: Res<FeedbackSounds>,
+) {
+    for hit in target_hits.read() {
+        let cue = if hit.killed { &cues.kill } else { &cues.hit };
+        if let Some(sound) = cue {
+            sound.play(&mut commands, &mut sounds);
+        }
+        if hit.headshot {
+            if let Some(sound) = &cues.headshot {
+                sound.play(&mut commands, &mut sounds);
+            }
+        }
+    }
+}
+
+fn play_miss_feedback(
+    mut commands: Commands,
+    mut shots_missed: EventReader<ShotMissed>,
+    mut sounds: SoundPlayer,
+    cues: Res<FeedbackSounds>,
+) {
+    for _ in shots_missed.read() {
+        if let Some(sound) = &cues.miss {
+            sound.play(&mut commands, &mut sounds);
+        }
+    }
+}
+
+fn emit_target_audio(
+    mut commands: Commands,
+    new_targets: Query<(Entity, &Transform), Added<Target>>,
+    mut sounds: SoundPlayer,
+    scenario: Res<Scenario>,
+) {
+    for (entity, transform) in &new_targets {
+        if scenario.target_audio.spawn_cue {
+            sounds.play(
+                &mut commands,
+                SoundEffect::TargetSpawn,
+                0.6,
+                1.0,
+                Some(transform.translation),
+            );
+        }
+        if scenario.target_audio.hum {
+            sounds.play_looping_on(&mut commands, entity, SoundEffect::TargetHum, 0.3);
+        }
+    }
+}
@@ -16,7 +16,7 @@ impl Plugin for FpsGunPlugin {
     }
 }
 
-#[derive(Component, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Component, Clone, Debug, Default, Eq, PartialEq, Hash)]
 pub struct GunAnimationState {
     pub walking: bool,
     pub shooting: bool,
@@ -35,6 +35,10 @@ pub struct ViewModelRenderPlayer;
 #[derive(Component)]
 pub struct FpsGunMuzzle;
 
+/// Points from a gun's root entity to the entity inside its scene that plays the animations.
+#[derive(Component)]
+pub struct FpsGunAnimationPlayer(pub Entity);
+
 #[derive(Component, Clone)]
 pub struct FpsGunAnimationsData {
     pub default_animation_index: usize,
@@ -84,11 +88,7 @@ pub struct LastPosition {
     pub last_position: Vec3,
 }
 
-fn setup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut graphs: ResMut<Assets<AnimationGraph>>,
-) {
+fn setup(mut commands: Commands) {
     commands.spawn((
         ViewModelRenderPlayer,
         Camera3d::default(),
@@ -105,14 +105,6 @@ fn setup(
         RenderLayers::layer(VIEW_MODEL_RENDER_LAYER),
     ));
 
-    // Spawn the gun
-    spawn_gun(
-        &mut commands,
-        &asset_server,
-        &mut graphs,
-        "ak47_animated.glb",
-    );
-
     commands.spawn((
         DirectionalLight {
             illuminance: light_consts::lux::AMBIENT_DAYLIGHT,
@@ -129,12 +121,15 @@ fn setup(
     ));
 }
 
-fn spawn_gun(
+/// Spawns an animated gun view model and returns its root entity.
+/// Each gun gets its own animation graph and `GunAnimationState`.
+pub fn spawn_gun(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
     graphs: &mut ResMut<Assets<AnimationGraph>>,
     asset_path: &str,
-) {
+    transform: Transform,
+) -> Entity {
     let (graph, node_indices) = AnimationGraph::from_clips(
         GunAnimations::all_indices()
             .iter()
@@ -157,15 +152,22 @@ fn spawn_gun(
     commands
         .spawn((
             SceneRoot(scene),
-            Transform {
-                translation: Vec3::new(1.0, -1.0, -1.5),
-                scale: Vec3::splat(0.15),
-                rotation: Quat::from_euler(EulerRot::XYX, 0.0, -PI, 0.0),
-            },
+            transform,
             RenderLayers::from_layers(&[VIEW_MODEL_RENDER_LAYER]),
             animations,
+            GunAnimationState::default(),
         ))
-        .observe(on_gun_scene_loaded);
+        .observe(on_gun_scene_loaded)
+        .id()
+}
+
+/// Where a gun view model sits in front of the view model camera.
+pub fn default_gun_transform() -> Transform {
+    Transform {
+        translation: Vec3::new(1.0, -1.0, -1.5),
+        scale: Vec3::splat(0.15),
+        rotation: Quat::from_euler(EulerRot::XYX, 0.0, -PI, 0.0),
+    }
 }
 
 fn on_gun_scene_loaded(
@@ -207,15 +209,10 @@ fn on_gun_scene_loaded(
     commands.entity(entity_to_animate).insert((
         AnimationGraphHandle(animations.graph.clone()),
         transitions,
-        GunAnimationState {
-            walking: false,
-            shooting: false,
-            reloading: false,
-            previous_walking: false,
-            previous_shooting: false,
-            previous_reloading: false,
-        },
     ));
+    commands
+        .entity(entity)
+        .insert(FpsGunAnimationPlayer(entity_to_animate));
 
     if let Some(muzzle) = find_entity(&children_query, &name_query, entity_to_animate, "Muzzle") {
         commands
@@ -247,7 +244,7 @@ fn move_listener(
     let current_position = transform.translation;
     let delta = current_position - last_position.last_position;
     last_position.last_position = current_position;
-    if let Ok(mut gun_animation_state) = gun_animation_state.get_single_mut() {
+    for mut gun_animation_state in &mut gun_animation_state {
         if delta.length_squared() > 0.02 * 0.02 {
             gun_animation_state.walking = true;
         } else {
@@ -257,19 +254,22 @@ fn move_listener(
 }
 
 fn on_fps_gun_animation(
-    mut animation_query: Query<(
-        &mut AnimationPlayer,
-        &mut AnimationTransitions,
+    mut animation_query: Query<(&mut AnimationPlayer, &mut AnimationTransitions)>,
+    mut guns: Query<(
+        &mut FpsGunAnimationsData,
         &mut GunAnimationState,
+        &FpsGunAnimationPlayer,
     )>,
-    mut animations: Query<&mut FpsGunAnimationsData>,
 ) {
-    if let Ok((mut animation_player, mut transitions, mut state)) = animation_query.get_single_mut()
-    {
+    for (mut animations, mut state, animation_player) in &mut guns {
+        let Ok((mut animation_player, mut transitions)) =
+            animation_query.get_mut(animation_player.0)
+        else {
+            continue;
+        };
         let previous_walking = state.previous_walking;
         let previous_shooting = state.previous_shooting;
 
-        let mut animations = animations.get_single_mut().unwrap();
         let mut duration = 0;
         let mut new_animation: Option<GunAnimations> = None;
         if state.shooting {
@@ -0,0 +1,271 @@
+use crate::adaptive::{Difficulty, DifficultyPoint};
+use crate::scenario::Scenario;
+use crate::skills::SkillWeights;
+use crate::stats::{RestartSession, SessionFinished, SessionStats};
+use crate::version::DrillVersion;
+use crate::TargetRng;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Finished sessions are appended to this file, one JSON record per line.
+const HISTORY_FILE: &str = "sessions.jsonl";
+
+/// Keeps a record of every finished session and shows how it compares with earlier runs of
+/// the same version of the drill.
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SessionHistory::load(PathBuf::from(HISTORY_FILE)));
+        app.add_systems(
+            Update,
+            (record_session, show_session_summary, hide_session_summary).chain(),
+        );
+    }
+}
+
+/// The result of one finished session.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub scenario: String,
+    /// `DrillVersion::id` of the definitions the session was played with.
+    pub version: String,
+    pub scenario_hash: String,
+    pub loadout_hash: String,
+    pub seed: u64,
+    pub score: f32,
+    pub accuracy: f32,
+    pub targets_killed: u32,
+    pub shots_fired: u32,
+    pub duration_secs: f32,
+    /// Seconds since the Unix epoch.
+    pub finished_at: u64,
+    /// How the difficulty of an adaptive scenario changed over the session.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub difficulty_curve: Vec<DifficultyPoint>,
+    /// Skills the scenario trains, as declared when the session was played.
+    #[serde(default)]
+    pub skills: SkillWeights,
+}
+
+/// Every session recorded on this machine.
+#[derive(Resource, Debug)]
+pub struct SessionHistory {
+    path: PathBuf,
+    pub records: Vec<SessionRecord>,
+}
+
+/// How a scenario's runs on one version compare with each other and with other versions.
+#[derive(Clone, Debug, Default)]
+pub struct VersionSummary {
+    pub runs: usize,
+    pub best_score: Option<f32>,
+    /// Runs of the same scenario on other versions, which are not compared.
+    pub other_version_runs: usize,
+    pub other_versions: usize,
+}
+
+impl SessionHistory {
+    /// Reads the history file, skipping lines that cannot be parsed.
+    pub fn load(path: PathBuf) -> Self {
+        let mut records = Vec::new();
+        match File::open(&path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines().map_while(Result::ok) {
+                    match serde_json::from_str(&line) {
+                        Ok(record) => records.push(record),
+                        Err(error) => eprintln!("Skipping session record: {}", error),
+                    }
+                }
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => eprintln!("Could not read {}: {}", path.display(), error),
+        }
+        SessionHistory { path, records }
+    }
+
+    pub fn append(&mut self, record: SessionRecord) -> io::Result<()> {
+        self.records.push(record.clone());
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        file.write_all(&line)
+    }
+
+    pub fn summary(&self, scenario: &str, version: &str) -> VersionSummary {
+        let mut summary = VersionSummary::default();
+        let mut other_versions = Vec::new();
+        for record in self
+            .records
+            .iter()
+            .filter(|record| record.scenario == scenario)
+        {
+            if record.version == version {
+                summary.runs += 1;
+                summary.best_score = Some(
+                    summary
+                        .best_score
+                        .map_or(record.score, |best| best.max(record.score)),
+                );
+            } else {
+                summary.other_version_runs += 1;
+                if !other_versions.contains(&&record.version) {
+                    other_versions.push(&record.version);
+                }
+            }
+        }
+        summary.other_versions = other_versions.len();
+        summary
+    }
+}
+
+/// Seconds since the Unix epoch, or zero if the clock is before it.
+pub fn unix_time_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs())
+}
+
+#[derive(Component)]
+struct SessionSummary;
+
+fn record_session(
+    mut session_finished: EventReader<SessionFinished>,
+    mut history: ResMut<SessionHistory>,
+    scenario: Res<Scenario>,
+    stats: Res<SessionStats>,
+    version: Res<DrillVersion>,
+    target_rng: Res<TargetRng>,
+    difficulty: Res<Difficulty>,
+) {
+    for _ in session_finished.read() {
+        let record = SessionRecord {
+            scenario: scenario.name.clone(),
+            version: version.id(),
+            scenario_hash: version.scenario.to_hex().to_string(),
+            loadout_hash: version.loadout.to_hex().to_string(),
+            seed: target_rng.seed,
+            score: scenario.score(&stats),
+            accuracy: stats.accuracy(),
+            targets_killed: stats.targets_killed,
+            shots_fired: stats.shots_fired,
+            duration_secs: stats.elapsed.elapsed_secs(),
+            finished_at: unix_time_secs(),
+            difficulty_curve: difficulty.curve.clone(),
+            skills: scenario.skills,
+        };
+        if let Err(error) = history.append(record) {
+            eprintln!("Could not save session: {}", error);
+        }
+    }
+}
+
+fn show_session_summary(
+    mut commands: Commands,
+    mut session_finished: EventReader<SessionFinished>,
+    history: Res<SessionHistory>,
+    scenario: Res<Scenario>,
+    stats: Res<SessionStats>,
+    version: Res<DrillVersion>,
+    difficulty: Res<Difficulty>,
+    summaries: Query<Entity, With<SessionSummary>>,
+) {
+    if session_finished.read().last().is_none() {
+        return;
+    }
+    for entity in &summaries {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    // The session just finished is already part of the history.
+    let summary = history.summary(&scenario.name, &version.id());
+    let mut lines = vec![
+        format!("{} complete", scenario.name),
+        format!(
+            "Score: {:.0}  |  Accuracy: {:.0}%",
+            scenario.score(&stats),
+            stats.accuracy() * 100.0
+        ),
+    ];
+    if let Some(best) = summary.best_score {
+        lines.push(format!(
+            "Best on version {}: {:.0} over {} runs",
+            version.short_id(),
+            best,
+            summary.runs
+        ));
+    }
+    if scenario.adaptive.is_some() {
+        let average = if difficulty.curve.is_empty() {
+            difficulty.level
+        } else {
+            difficulty
+                .curve
+                .iter()
+                .map(|point| point.level)
+                .sum::<f32>()
+                / difficulty.curve.len() as f32
+        };
+        lines.push(format!(
+            "Difficulty: {:.2} at the end, {:.2} on average",
+            difficulty.level, average
+        ));
+    }
+    if summary.other_version_runs > 0 {
+        lines.push(format!(
+            "{} runs on {} other versions are not compared",
+            summary.other_version_runs, summary.other_versions
+        ));
+    }
+
+    commands
+        .spawn((
+            SessionSummary,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(20.0)),
+                        row_gap: Val::Px(6.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+                ))
+                .with_children(|panel| {
+                    for line in lines {
+                        panel.spawn(Text::new(line));
+                    }
+                });
+        });
+}
+
+fn hide_session_summary(
+    mut commands: Commands,
+    mut restarts: EventReader<RestartSession>,
+    summaries: Query<Entity, With<SessionSummary>>,
+) {
+    if restarts.read().last().is_none() {
+        return;
+    }
+    for entity in &summaries {
+        commands.entity(entity).despawn_recursive();
+    }
+}
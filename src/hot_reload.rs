@@ -0,0 +1,316 @@
+use crate::audio::AudioVolumes;
+use crate::crosshair::Crosshair;
+use crate::hud::HudConfig;
+use crate::scenario::Scenario;
+use crate::stats::RestartSession;
+use crate::version::definition_hash;
+use crate::weapon::{Loadout, Weapon};
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::fmt;
+use std::marker::PhantomData;
+use std::path::Path;
+
+/// Assets are read from here by the asset server.
+const ASSET_DIRECTORY: &str = "assets";
+/// A loadout replacing the built in weapons, relative to the asset directory.
+const WEAPONS_FILE: &str = "weapons/default.weapons.ron";
+/// Crosshair settings replacing the defaults, relative to the asset directory.
+const CROSSHAIR_FILE: &str = "settings/default.crosshair.ron";
+/// Volumes of the audio buses, relative to the asset directory.
+const AUDIO_FILE: &str = "settings/default.audio.ron";
+/// Which HUD widgets are shown and where, relative to the asset directory.
+const HUD_FILE: &str = "settings/default.hud.ron";
+
+/// Watches the scenario, weapon, crosshair, audio and HUD files and applies changes to them while
+/// the game runs.
+///
+/// - A changed scenario replaces the current one and restarts the session. The arena is rebuilt
+///   if it changed, while the loadout and crosshair are kept.
+/// - A changed loadout applies to the next shot of each weapon. Ammo above the new magazine
+///   size is dropped. View models are not swapped, so a new `model` needs a restart.
+/// - A changed crosshair is redrawn straight away.
+/// - Changed volumes apply to sounds already playing.
+/// - A changed HUD layout is rebuilt straight away.
+///
+/// The scenario is only watched when it was loaded from a file, see `ScenarioPath`. The loadout,
+/// crosshair, audio and HUD files are the player's own overrides: the built in values apply unless the file
+/// exists when the game starts, so there is nothing to go stale when the defaults change.
+pub struct HotReloadPlugin;
+
+impl Plugin for HotReloadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ScenarioAsset>()
+            .init_asset::<LoadoutAsset>()
+            .init_asset::<CrosshairAsset>()
+            .init_asset::<AudioAsset>()
+            .init_asset::<HudAsset>()
+            .register_asset_loader(RonAssetLoader::<ScenarioAsset>::new(&["scenario.ron"]))
+            .register_asset_loader(RonAssetLoader::<LoadoutAsset>::new(&["weapons.ron"]))
+            .register_asset_loader(RonAssetLoader::<CrosshairAsset>::new(&["crosshair.ron"]))
+            .register_asset_loader(RonAssetLoader::<AudioAsset>::new(&["audio.ron"]))
+            .register_asset_loader(RonAssetLoader::<HudAsset>::new(&["hud.ron"]));
+        app.add_systems(Startup, watch_files);
+        app.add_systems(
+            Update,
+            (
+                reload_scenario,
+                reload_loadout,
+                reload_crosshair,
+                reload_audio,
+                reload_hud,
+            )
+                .run_if(resource_exists::<WatchedFiles>),
+        );
+    }
+}
+
+/// Where the scenario was loaded from, relative to the asset directory. `None` for built in
+/// scenarios.
+#[derive(Resource)]
+pub struct ScenarioPath(pub Option<String>);
+
+/// A scenario read from a `.scenario.ron` file.
+#[derive(Asset, TypePath, Deserialize)]
+#[serde(transparent)]
+pub struct ScenarioAsset(pub Scenario);
+
+/// A loadout read from a `.weapons.ron` file.
+#[derive(Asset, TypePath, Deserialize)]
+#[serde(transparent)]
+pub struct LoadoutAsset(pub Loadout);
+
+/// Crosshair settings read from a `.crosshair.ron` file.
+#[derive(Asset, TypePath, Deserialize)]
+#[serde(transparent)]
+pub struct CrosshairAsset(pub Crosshair);
+
+/// Bus volumes read from a `.audio.ron` file.
+#[derive(Asset, TypePath, Deserialize)]
+#[serde(transparent)]
+pub struct AudioAsset(pub AudioVolumes);
+
+/// HUD layout read from a `.hud.ron` file.
+#[derive(Asset, TypePath, Deserialize)]
+#[serde(transparent)]
+pub struct HudAsset(pub HudConfig);
+
+/// Loads any asset that can be deserialised from RON.
+struct RonAssetLoader<A> {
+    extensions: &'static [&'static str],
+    marker: PhantomData<fn() -> A>,
+}
+
+impl<A> RonAssetLoader<A> {
+    fn new(extensions: &'static [&'static str]) -> Self {
+        RonAssetLoader {
+            extensions,
+            marker: PhantomData,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RonAssetError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl fmt::Display for RonAssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RonAssetError::Io(error) => write!(f, "{}", error),
+            RonAssetError::Ron(error) => write!(f, "invalid RON: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for RonAssetError {}
+
+impl<A: Asset + DeserializeOwned> AssetLoader for RonAssetLoader<A> {
+    type Asset = A;
+    type Settings = ();
+    type Error = RonAssetError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<A, RonAssetError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(RonAssetError::Io)?;
+        ron::de::from_bytes(&bytes).map_err(RonAssetError::Ron)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
+
+/// Loads `path` under the asset directory if the player has created it.
+fn load_override<A: Asset>(asset_server: &AssetServer, path: &str) -> Option<Handle<A>> {
+    if !Path::new(ASSET_DIRECTORY).join(path).exists() {
+        return None;
+    }
+    println!("Using {}", path);
+    Some(asset_server.load(path))
+}
+
+/// Handles to the watched files. Holding them keeps the assets loaded.
+#[derive(Resource)]
+struct WatchedFiles {
+    scenario: Option<Handle<ScenarioAsset>>,
+    loadout: Option<Handle<LoadoutAsset>>,
+    crosshair: Option<Handle<CrosshairAsset>>,
+    audio: Option<Handle<AudioAsset>>,
+    hud: Option<Handle<HudAsset>>,
+}
+
+fn watch_files(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    scenario_path: Res<ScenarioPath>,
+) {
+    commands.insert_resource(WatchedFiles {
+        scenario: scenario_path.0.clone().map(|path| asset_server.load(path)),
+        loadout: load_override(&asset_server, WEAPONS_FILE),
+        crosshair: load_override(&asset_server, CROSSHAIR_FILE),
+        audio: load_override(&asset_server, AUDIO_FILE),
+        hud: load_override(&asset_server, HUD_FILE),
+    });
+}
+
+/// The id of the asset an event is about, if it was loaded or changed.
+fn loaded_or_modified<A: Asset>(event: &AssetEvent<A>) -> Option<AssetId<A>> {
+    match event {
+        AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => Some(*id),
+        _ => None,
+    }
+}
+
+/// Replaces the scenario when its file changes. The first load is skipped, as the scenario was
+/// already read when the game started.
+fn reload_scenario(
+    mut events: EventReader<AssetEvent<ScenarioAsset>>,
+    files: Res<WatchedFiles>,
+    assets: Res<Assets<ScenarioAsset>>,
+    mut scenario: ResMut<Scenario>,
+    mut restarts: EventWriter<RestartSession>,
+) {
+    let Some(handle) = &files.scenario else {
+        return;
+    };
+    for event in events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        if *id != handle.id() {
+            continue;
+        }
+        let Some(ScenarioAsset(new_scenario)) = assets.get(*id) else {
+            continue;
+        };
+        // Saving from the editor writes the scenario being played, which needs no restart.
+        if new_scenario.name == scenario.name
+            && definition_hash("scenario", new_scenario) == definition_hash("scenario", &*scenario)
+        {
+            continue;
+        }
+        println!("Reloaded scenario {}", new_scenario.name);
+        *scenario = new_scenario.clone();
+        restarts.send(RestartSession);
+    }
+}
+
+fn reload_loadout(
+    mut events: EventReader<AssetEvent<LoadoutAsset>>,
+    files: Res<WatchedFiles>,
+    assets: Res<Assets<LoadoutAsset>>,
+    mut loadout: ResMut<Loadout>,
+    mut weapons: Query<&mut Weapon>,
+) {
+    let Some(handle) = &files.loadout else {
+        return;
+    };
+    for id in events.read().filter_map(loaded_or_modified) {
+        if id != handle.id() {
+            continue;
+        }
+        let Some(LoadoutAsset(new_loadout)) = assets.get(id) else {
+            continue;
+        };
+        *loadout = new_loadout.clone();
+        for mut weapon in &mut weapons {
+            weapon.stats = loadout.get(weapon.slot).clone();
+            weapon.ammo = weapon.ammo.min(weapon.stats.magazine_size);
+        }
+    }
+}
+
+fn reload_crosshair(
+    mut events: EventReader<AssetEvent<CrosshairAsset>>,
+    files: Res<WatchedFiles>,
+    assets: Res<Assets<CrosshairAsset>>,
+    mut crosshair: ResMut<Crosshair>,
+) {
+    let Some(handle) = &files.crosshair else {
+        return;
+    };
+    for id in events.read().filter_map(loaded_or_modified) {
+        if id != handle.id() {
+            continue;
+        }
+        if let Some(CrosshairAsset(new_crosshair)) = assets.get(id) {
+            *crosshair = new_crosshair.clone();
+        }
+    }
+}
+
+fn reload_audio(
+    mut events: EventReader<AssetEvent<AudioAsset>>,
+    files: Res<WatchedFiles>,
+    assets: Res<Assets<AudioAsset>>,
+    mut volumes: ResMut<AudioVolumes>,
+) {
+    let Some(handle) = &files.audio else {
+        return;
+    };
+    for id in events.read().filter_map(loaded_or_modified) {
+        if id != handle.id() {
+            continue;
+        }
+        if let Some(AudioAsset(new_volumes)) = assets.get(id) {
+            *volumes = new_volumes.clone();
+        }
+    }
+}
+
+/// Applies a changed HUD layout, keeping the minimal mode as toggled in game.
+fn reload_hud(
+    mut events: EventReader<AssetEvent<HudAsset>>,
+    files: Res<WatchedFiles>,
+    assets: Res<Assets<HudAsset>>,
+    mut config: ResMut<HudConfig>,
+) {
+    let Some(handle) = &files.hud else {
+        return;
+    };
+    for id in events.read().filter_map(loaded_or_modified) {
+        if id != handle.id() {
+            continue;
+        }
+        if let Some(HudAsset(new_config)) = assets.get(id) {
+            *config = HudConfig {
+                minimal: config.minimal,
+                ..new_config.clone()
+            };
+        }
+    }
+}
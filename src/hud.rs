@@ -0,0 +1,299 @@
+use crate::adaptive::Difficulty;
+use crate::audio::{SoundEffect, SoundPlayer};
+use crate::scenario::Scenario;
+use crate::stats::SessionStats;
+use crate::weapon::{Weapon, WeaponSwitch};
+use crate::TargetHit;
+use bevy::prelude::*;
+use bevy::time::Stopwatch;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_4;
+
+/// How long the hit marker stays on screen after a hit.
+const HIT_MARKER_SECS: f32 = 0.12;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HudConfig::default());
+        app.add_systems(
+            Update,
+            (
+                (toggle_minimal_mode, build_hud, update_hud_text).chain(),
+                show_hit_marker,
+                hide_hit_marker,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Which HUD widgets are shown, and where. Set from the player's HUD settings file, see
+/// `HotReloadPlugin`, and the HUD is rebuilt whenever it changes.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HudConfig {
+    pub timer: HudWidget,
+    pub score: HudWidget,
+    pub accuracy: HudWidget,
+    pub kills_per_second: HudWidget,
+    pub ammo: HudWidget,
+    pub streak: HudWidget,
+    /// Only shown in adaptive scenarios.
+    pub difficulty: HudWidget,
+    pub hit_marker: bool,
+    /// Hides every text widget, leaving only the crosshair and hit marker. Toggled with H.
+    pub minimal: bool,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct HudWidget {
+    pub enabled: bool,
+    pub anchor: HudAnchor,
+}
+
+/// Screen corner or edge a widget is stacked against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HudAnchor {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Default for HudConfig {
+    fn default() -> Self {
+        HudConfig {
+            timer: HudWidget::new(HudAnchor::TopCenter),
+            score: HudWidget::new(HudAnchor::BottomLeft),
+            accuracy: HudWidget::new(HudAnchor::BottomLeft),
+            kills_per_second: HudWidget::new(HudAnchor::TopRight),
+            ammo: HudWidget::new(HudAnchor::BottomRight),
+            streak: HudWidget::new(HudAnchor::TopRight),
+            difficulty: HudWidget::new(HudAnchor::TopRight),
+            hit_marker: true,
+            minimal: false,
+        }
+    }
+}
+
+impl HudWidget {
+    pub fn new(anchor: HudAnchor) -> Self {
+        HudWidget {
+            enabled: true,
+            anchor,
+        }
+    }
+}
+
+impl HudAnchor {
+    fn node(&self) -> Node {
+        let mut node = Node {
+            position_type: PositionType::Absolute,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        };
+        match self {
+            HudAnchor::TopLeft => {
+                node.top = Val::Px(5.0);
+                node.left = Val::Px(15.0);
+            }
+            HudAnchor::TopCenter => {
+                node.top = Val::Px(5.0);
+                node.width = Val::Percent(100.0);
+                node.align_items = AlignItems::Center;
+            }
+            HudAnchor::TopRight => {
+                node.top = Val::Px(5.0);
+                node.right = Val::Px(15.0);
+                node.align_items = AlignItems::FlexEnd;
+            }
+            HudAnchor::BottomLeft => {
+                node.bottom = Val::Px(5.0);
+                node.left = Val::Px(15.0);
+            }
+            HudAnchor::BottomRight => {
+                node.bottom = Val::Px(5.0);
+                node.right = Val::Px(15.0);
+                node.align_items = AlignItems::FlexEnd;
+            }
+        }
+        node
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum HudText {
+    Timer,
+    Score,
+    Accuracy,
+    KillsPerSecond,
+    Ammo,
+    Streak,
+    Difficulty,
+}
+
+/// A column of text widgets stacked against one anchor.
+#[derive(Component)]
+struct HudPanel;
+
+#[derive(Component)]
+struct HitMarker {
+    stopwatch: Stopwatch,
+    material: Handle<ColorMaterial>,
+}
+
+/// Lays the HUD out again from the configuration whenever it changes.
+fn build_hud(
+    mut commands: Commands,
+    config: Res<HudConfig>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    roots: Query<Entity, Or<(With<HudPanel>, With<HitMarker>)>>,
+) {
+    if !config.is_changed() {
+        return;
+    }
+    for root in &roots {
+        commands.entity(root).despawn_recursive();
+    }
+
+    let panel_visibility = if config.minimal {
+        Visibility::Hidden
+    } else {
+        Visibility::Inherited
+    };
+    let widgets = [
+        (HudText::Timer, config.timer),
+        (HudText::Score, config.score),
+        (HudText::Accuracy, config.accuracy),
+        (HudText::KillsPerSecond, config.kills_per_second),
+        (HudText::Ammo, config.ammo),
+        (HudText::Streak, config.streak),
+        (HudText::Difficulty, config.difficulty),
+    ];
+    let mut panels = HashMap::default();
+    for (text, widget) in widgets {
+        if !widget.enabled {
+            continue;
+        }
+        let panel = *panels.entry(widget.anchor).or_insert_with(|| {
+            commands
+                .spawn((widget.anchor.node(), HudPanel, panel_visibility))
+                .id()
+        });
+        commands.entity(panel).with_child((Text::new(""), text));
+    }
+
+    if config.hit_marker {
+        // Four short strokes forming an X around the crosshair.
+        let material = materials.add(Color::WHITE);
+        let stroke = meshes.add(Rectangle::new(2.0, 8.0));
+        commands
+            .spawn((
+                HitMarker {
+                    stopwatch: Stopwatch::new(),
+                    material: material.clone(),
+                },
+                Transform::default(),
+                Visibility::Hidden,
+            ))
+            .with_children(|parent| {
+                for quadrant in 0..4 {
+                    let angle = FRAC_PI_4 + quadrant as f32 * 2.0 * FRAC_PI_4;
+                    let rotation = Quat::from_rotation_z(angle);
+                    parent.spawn((
+                        Mesh2d(stroke.clone()),
+                        MeshMaterial2d(material.clone()),
+                        Transform::from_translation(rotation * Vec3::new(0.0, 9.0, 0.0))
+                            .with_rotation(rotation),
+                    ));
+                }
+            });
+    }
+}
+
+fn update_hud_text(
+    stats: Res<SessionStats>,
+    scenario: Res<Scenario>,
+    difficulty: Res<Difficulty>,
+    switch: Res<WeaponSwitch>,
+    weapons: Query<&Weapon>,
+    mut texts: Query<(&HudText, &mut Text)>,
+) {
+    for (hud_text, mut text) in &mut texts {
+        text.0 = match hud_text {
+            HudText::Timer => {
+                let remaining = (scenario.duration_secs - stats.elapsed.elapsed_secs()).max(0.0);
+                let seconds = remaining.ceil() as u32;
+                format!("{}:{:02}", seconds / 60, seconds % 60)
+            }
+            HudText::Score => format!("Score: {:.0}", scenario.score(&stats)),
+            HudText::Accuracy => format!("Accuracy: {:.0}%", stats.accuracy() * 100.0),
+            HudText::KillsPerSecond => {
+                format!("Kills/s: {:.2}", stats.kills_per_minute() / 60.0)
+            }
+            HudText::Ammo => match weapons.iter().find(|weapon| weapon.slot == switch.active) {
+                Some(weapon) if weapon.reload.is_some() => {
+                    format!("{}: Reloading", weapon.stats.name)
+                }
+                Some(weapon) => format!(
+                    "{}: {}/{}",
+                    weapon.stats.name, weapon.ammo, weapon.stats.magazine_size
+                ),
+                None => String::new(),
+            },
+            HudText::Streak => format!("Streak: {}", stats.streak),
+            HudText::Difficulty if scenario.adaptive.is_some() => {
+                format!("Difficulty: {:.2}", difficulty.level)
+            }
+            HudText::Difficulty => String::new(),
+        };
+    }
+}
+
+fn toggle_minimal_mode(
+    mut commands: Commands,
+    key: Res<ButtonInput<KeyCode>>,
+    mut config: ResMut<HudConfig>,
+    mut sounds: SoundPlayer,
+) {
+    if key.just_pressed(KeyCode::KeyH) {
+        config.minimal = !config.minimal;
+        sounds.play(&mut commands, SoundEffect::UiClick, 0.5, 1.0, None);
+    }
+}
+
+fn show_hit_marker(
+    mut target_hits: EventReader<TargetHit>,
+    mut hit_marker: Query<(&mut HitMarker, &mut Visibility)>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for hit in target_hits.read() {
+        if let Ok((mut marker, mut visibility)) = hit_marker.get_single_mut() {
+            marker.stopwatch.reset();
+            *visibility = Visibility::Inherited;
+            if let Some(material) = materials.get_mut(&marker.material) {
+                material.color = if hit.killed {
+                    Color::srgb(1.0, 0.2, 0.2)
+                } else if hit.headshot {
+                    Color::srgb(1.0, 0.85, 0.2)
+                } else {
+                    Color::WHITE
+                };
+            }
+        }
+    }
+}
+
+fn hide_hit_marker(mut hit_marker: Query<(&mut HitMarker, &mut Visibility)>, time: Res<Time>) {
+    for (mut marker, mut visibility) in &mut hit_marker {
+        marker.stopwatch.tick(time.delta());
+        if marker.stopwatch.elapsed_secs() > HIT_MARKER_SECS {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
@@ -0,0 +1,606 @@
+use crate::history::unix_time_secs;
+use crate::scenario::Scenario;
+use crate::stats::{SessionFinished, SessionStats};
+use crate::version::DrillVersion;
+use crate::TargetRng;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Scores are kept in this file unless a leaderboard server is configured.
+const DEFAULT_LEADERBOARD_FILE: &str = "leaderboard.jsonl";
+/// Base URL of a leaderboard server, such as `http://127.0.0.1:8080/api`.
+const URL_VARIABLE: &str = "AIM_TRAINER_LEADERBOARD_URL";
+/// Secret shared with the leaderboard server, used to sign submissions.
+const KEY_VARIABLE: &str = "AIM_TRAINER_LEADERBOARD_KEY";
+/// Name submitted with scores.
+const PLAYER_VARIABLE: &str = "AIM_TRAINER_PLAYER";
+/// Context string for deriving signing keys from the shared secret.
+const KEY_CONTEXT: &str = "aim trainer leaderboard 2025-01-01 submission signing";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Submits the session's score to the configured leaderboard when it finishes. Nothing is
+/// submitted unless `AIM_TRAINER_LEADERBOARD_KEY` is set, as unsigned scores could be forged.
+pub struct LeaderboardPlugin;
+
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(leaderboard) = Leaderboard::from_env() {
+            app.insert_resource(leaderboard);
+        }
+        app.add_systems(
+            Update,
+            (
+                submit_finished_session.run_if(resource_exists::<Leaderboard>),
+                skip_unsigned_session.run_if(not(resource_exists::<Leaderboard>)),
+            ),
+        );
+    }
+}
+
+/// A finished session, as reported to a leaderboard.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Submission {
+    pub player: String,
+    pub scenario: String,
+    /// `DrillVersion::id` of the scenario and weapon definitions the score was set on, so only
+    /// scores set on the same version are ranked together.
+    pub scenario_hash: String,
+    /// Seed of the target layout.
+    pub seed: u64,
+    pub score: f32,
+    /// Checksum of the session's recorded stats.
+    pub replay_checksum: String,
+    /// Seconds since the Unix epoch.
+    pub submitted_at: u64,
+}
+
+/// A submission with a keyed hash proving it came from a client holding the shared secret.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignedSubmission {
+    pub submission: Submission,
+    pub signature: String,
+}
+
+impl Submission {
+    pub fn sign(self, key: &[u8; 32]) -> SignedSubmission {
+        let signature = signature(&self, key).to_hex().to_string();
+        SignedSubmission {
+            submission: self,
+            signature,
+        }
+    }
+}
+
+impl SignedSubmission {
+    pub fn verify(&self, key: &[u8; 32]) -> bool {
+        let Ok(claimed) = blake3::Hash::from_hex(&self.signature) else {
+            return false;
+        };
+        // Hash equality is constant time.
+        claimed == signature(&self.submission, key)
+    }
+}
+
+fn signature(submission: &Submission, key: &[u8; 32]) -> blake3::Hash {
+    // Field order is fixed by the struct, so the encoding is stable.
+    let encoded = serde_json::to_vec(submission).expect("submissions always serialize");
+    blake3::keyed_hash(key, &encoded)
+}
+
+/// Derives the signing key from a shared secret.
+pub fn signing_key(secret: &str) -> [u8; 32] {
+    blake3::derive_key(KEY_CONTEXT, secret.as_bytes())
+}
+
+#[derive(Debug)]
+pub enum LeaderboardError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// The server answered with something other than success.
+    Http {
+        status: u16,
+        body: String,
+    },
+    /// The response was not valid HTTP.
+    Protocol(String),
+}
+
+impl fmt::Display for LeaderboardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LeaderboardError::Io(error) => write!(f, "{}", error),
+            LeaderboardError::Json(error) => write!(f, "invalid JSON: {}", error),
+            LeaderboardError::Http { status, body } => {
+                write!(f, "server answered {}: {}", status, body)
+            }
+            LeaderboardError::Protocol(message) => write!(f, "bad response: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for LeaderboardError {}
+
+impl From<io::Error> for LeaderboardError {
+    fn from(error: io::Error) -> Self {
+        LeaderboardError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for LeaderboardError {
+    fn from(error: serde_json::Error) -> Self {
+        LeaderboardError::Json(error)
+    }
+}
+
+/// Somewhere scores can be submitted to and read back from.
+pub trait LeaderboardBackend: Send + Sync {
+    fn submit(&self, submission: &SignedSubmission) -> Result<(), LeaderboardError>;
+
+    /// The best `limit` scores set on the scenario with `scenario_hash`, best first.
+    fn top(&self, scenario_hash: &str, limit: usize) -> Result<Vec<Submission>, LeaderboardError>;
+}
+
+/// Keeps scores in a local file, one JSON submission per line. Lines with a bad signature
+/// are left out of rankings.
+pub struct LocalFileLeaderboard {
+    pub path: PathBuf,
+    pub key: [u8; 32],
+}
+
+impl LeaderboardBackend for LocalFileLeaderboard {
+    fn submit(&self, submission: &SignedSubmission) -> Result<(), LeaderboardError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let mut line = serde_json::to_vec(submission)?;
+        line.push(b'\n');
+        file.write_all(&line)?;
+        Ok(())
+    }
+
+    fn top(&self, scenario_hash: &str, limit: usize) -> Result<Vec<Submission>, LeaderboardError> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        };
+        let mut scores = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let signed: SignedSubmission = match serde_json::from_str(&line) {
+                Ok(signed) => signed,
+                Err(error) => {
+                    eprintln!("Skipping leaderboard entry: {}", error);
+                    continue;
+                }
+            };
+            if signed.verify(&self.key) && signed.submission.scenario_hash == scenario_hash {
+                scores.push(signed.submission);
+            }
+        }
+        Ok(best_first(scores, limit))
+    }
+}
+
+fn best_first(mut scores: Vec<Submission>, limit: usize) -> Vec<Submission> {
+    scores.sort_by(|a, b| b.score.total_cmp(&a.score));
+    scores.truncate(limit);
+    scores
+}
+
+/// Talks JSON to a leaderboard server over plain HTTP/1.1. Submissions are `POST`ed to
+/// `{base}/submissions` and rankings read from `GET {base}/scores/{scenario_hash}?limit=n`,
+/// which answers with an array of submissions.
+pub struct HttpLeaderboard {
+    host: String,
+    port: u16,
+    base_path: String,
+}
+
+impl HttpLeaderboard {
+    /// Parses a base URL of the form `http://host[:port][/path]`.
+    pub fn new(url: &str) -> Option<Self> {
+        let rest = url.strip_prefix("http://")?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().ok()?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return None;
+        }
+        Some(HttpLeaderboard {
+            host: host.to_string(),
+            port,
+            base_path: path.trim_end_matches('/').to_string(),
+        })
+    }
+
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&[u8]>,
+    ) -> Result<Vec<u8>, LeaderboardError> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        stream.set_read_timeout(Some(HTTP_TIMEOUT))?;
+        stream.set_write_timeout(Some(HTTP_TIMEOUT))?;
+
+        let mut request = format!(
+            "{} {}{} HTTP/1.1\r\nHost: {}:{}\r\nAccept: application/json\r\nConnection: close\r\n",
+            method, self.base_path, path, self.host, self.port
+        )
+        .into_bytes();
+        if let Some(body) = body {
+            request.extend_from_slice(
+                format!(
+                    "Content-Type: application/json\r\nContent-Length: {}\r\n",
+                    body.len()
+                )
+                .as_bytes(),
+            );
+        }
+        request.extend_from_slice(b"\r\n");
+        if let Some(body) = body {
+            request.extend_from_slice(body);
+        }
+        stream.write_all(&request)?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        let (status, body) = parse_response(&response)?;
+        if !(200..300).contains(&status) {
+            return Err(LeaderboardError::Http {
+                status,
+                body: String::from_utf8_lossy(&body).into_owned(),
+            });
+        }
+        Ok(body)
+    }
+}
+
+impl LeaderboardBackend for HttpLeaderboard {
+    fn submit(&self, submission: &SignedSubmission) -> Result<(), LeaderboardError> {
+        let body = serde_json::to_vec(submission)?;
+        self.request("POST", "/submissions", Some(&body))?;
+        Ok(())
+    }
+
+    fn top(&self, scenario_hash: &str, limit: usize) -> Result<Vec<Submission>, LeaderboardError> {
+        let path = format!("/scores/{}?limit={}", scenario_hash, limit);
+        let body = self.request("GET", &path, None)?;
+        let scores: Vec<Submission> = serde_json::from_slice(&body)?;
+        Ok(best_first(scores, limit))
+    }
+}
+
+/// Splits a complete HTTP/1.1 response into its status code and body.
+fn parse_response(response: &[u8]) -> Result<(u16, Vec<u8>), LeaderboardError> {
+    let protocol_error = |message: &str| LeaderboardError::Protocol(message.to_string());
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| protocol_error("no end of headers"))?;
+    let head = std::str::from_utf8(&response[..header_end])
+        .map_err(|_| protocol_error("headers are not UTF-8"))?;
+    let body = &response[header_end + 4..];
+
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|status_line| status_line.split(' ').nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| protocol_error("no status code"))?;
+    let mut chunked = false;
+    let mut content_length = None;
+    for (name, value) in lines.filter_map(|line| line.split_once(':')) {
+        let (name, value) = (name.trim(), value.trim());
+        if name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = value.eq_ignore_ascii_case("chunked");
+        } else if name.eq_ignore_ascii_case("content-length") {
+            let length: usize = value
+                .parse()
+                .map_err(|_| protocol_error("bad content length"))?;
+            content_length = Some(length);
+        }
+    }
+    // Chunked encoding takes precedence over a length, as HTTP/1.1 requires.
+    let body = match content_length {
+        _ if chunked => decode_chunked(body).ok_or_else(|| protocol_error("bad chunked body"))?,
+        Some(length) => body
+            .get(..length)
+            .ok_or_else(|| protocol_error("body shorter than its content length"))?
+            .to_vec(),
+        None => body.to_vec(),
+    };
+    Ok((status, body))
+}
+
+fn decode_chunked(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|window| window == b"\r\n")?;
+        let size_line = std::str::from_utf8(&body[..line_end]).ok()?;
+        // Chunk extensions follow a semicolon.
+        let size_field = size_line.split(';').next()?.trim();
+        let size = usize::from_str_radix(size_field, 16).ok()?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Some(decoded);
+        }
+        decoded.extend_from_slice(body.get(..size)?);
+        body = body.get(size + 2..)?;
+    }
+}
+
+/// The leaderboard this session reports to.
+#[derive(Resource, Clone)]
+pub struct Leaderboard {
+    pub backend: Arc<dyn LeaderboardBackend>,
+    pub key: [u8; 32],
+    pub player: String,
+}
+
+impl Leaderboard {
+    /// Uses the server at `AIM_TRAINER_LEADERBOARD_URL` if set, and a local file otherwise.
+    /// `None` without a signing secret.
+    pub fn from_env() -> Option<Self> {
+        let secret = std::env::var(KEY_VARIABLE)
+            .ok()
+            .filter(|secret| !secret.is_empty())?;
+        let key = signing_key(&secret);
+        let player = std::env::var(PLAYER_VARIABLE)
+            .or_else(|_| std::env::var("USER"))
+            .unwrap_or_else(|_| "player".to_string());
+
+        let server = std::env::var(URL_VARIABLE).ok().and_then(|url| {
+            let server = HttpLeaderboard::new(&url);
+            if server.is_none() {
+                eprintln!("Leaderboard URL {:?} is not an http:// URL", url);
+            }
+            server
+        });
+        let backend: Arc<dyn LeaderboardBackend> = match server {
+            Some(server) => Arc::new(server),
+            None => Arc::new(LocalFileLeaderboard {
+                path: PathBuf::from(DEFAULT_LEADERBOARD_FILE),
+                key,
+            }),
+        };
+        Some(Leaderboard {
+            backend,
+            key,
+            player,
+        })
+    }
+}
+
+fn submit_finished_session(
+    mut session_finished: EventReader<SessionFinished>,
+    leaderboard: Res<Leaderboard>,
+    scenario: Res<Scenario>,
+    stats: Res<SessionStats>,
+    target_rng: Res<TargetRng>,
+    version: Res<DrillVersion>,
+) {
+    for _ in session_finished.read() {
+        let submission = Submission {
+            player: leaderboard.player.clone(),
+            scenario: scenario.name.clone(),
+            scenario_hash: version.id(),
+            seed: target_rng.seed,
+            score: scenario.score(&stats),
+            replay_checksum: stats.checksum().to_hex().to_string(),
+            submitted_at: unix_time_secs(),
+        }
+        .sign(&leaderboard.key);
+
+        // Network and disk access stay off the frame.
+        let backend = leaderboard.backend.clone();
+        std::thread::spawn(move || {
+            let hash = submission.submission.scenario_hash.clone();
+            if let Err(error) = backend.submit(&submission) {
+                eprintln!("Could not submit score: {}", error);
+                return;
+            }
+            println!("Submitted score {:.0}", submission.submission.score);
+            match backend.top(&hash, 5) {
+                Ok(top) => {
+                    for (rank, entry) in top.iter().enumerate() {
+                        println!("{}. {} {:.0}", rank + 1, entry.player, entry.score);
+                    }
+                }
+                Err(error) => eprintln!("Could not read leaderboard: {}", error),
+            }
+        });
+    }
+}
+
+/// Says why the first finished session is not submitted.
+fn skip_unsigned_session(
+    mut session_finished: EventReader<SessionFinished>,
+    mut warned: Local<bool>,
+) {
+    if session_finished.read().last().is_none() || *warned {
+        return;
+    }
+    *warned = true;
+    eprintln!(
+        "Not submitting scores, as {} is not set to sign them",
+        KEY_VARIABLE
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn submission(player: &str, score: f32) -> Submission {
+        Submission {
+            player: player.to_string(),
+            scenario: String::from("gridshot"),
+            scenario_hash: String::from("abc"),
+            seed: 7,
+            score,
+            replay_checksum: String::from("00"),
+            submitted_at: 1_700_000_000,
+        }
+    }
+
+    /// Serves `responses` in order, one per connection, and passes back each request received.
+    fn mock_server(responses: Vec<Vec<u8>>) -> (HttpLeaderboard, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (requests, received) = mpsc::channel();
+        thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                requests.send(read_request(&mut stream)).unwrap();
+                stream.write_all(&response).unwrap();
+            }
+        });
+        let url = format!("http://127.0.0.1:{}/api", port);
+        (HttpLeaderboard::new(&url).unwrap(), received)
+    }
+
+    fn read_request(stream: &mut TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        loop {
+            let read = stream.read(&mut buffer).unwrap();
+            if read == 0 {
+                return String::from_utf8_lossy(&request).into_owned();
+            }
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request);
+            let Some(header_end) = text.find("\r\n\r\n") else {
+                continue;
+            };
+            let length = text[..header_end]
+                .lines()
+                .find_map(|line| line.strip_prefix("Content-Length: "))
+                .map_or(0, |length| length.parse().unwrap());
+            if request.len() >= header_end + 4 + length {
+                return text.into_owned();
+            }
+        }
+    }
+
+    #[test]
+    fn submits_and_reads_scores_from_a_mock_server() {
+        let scores =
+            serde_json::to_string(&vec![submission("a", 10.0), submission("b", 30.0)]).unwrap();
+        // The chunked body is split mid-JSON, with an extension on the first chunk.
+        let (first, second) = scores.split_at(10);
+        let chunked = format!(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x};ext=1\r\n{}\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+            first.len(),
+            first,
+            second.len(),
+            second
+        );
+        let (server, requests) = mock_server(vec![
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}".to_vec(),
+            chunked.into_bytes(),
+        ]);
+
+        let signed = submission("a", 10.0).sign(&signing_key("secret"));
+        server.submit(&signed).unwrap();
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("POST /api/submissions HTTP/1.1\r\n"));
+        let body = &request[request.find("\r\n\r\n").unwrap() + 4..];
+        assert_eq!(
+            serde_json::from_str::<SignedSubmission>(body).unwrap(),
+            signed
+        );
+
+        let top = server.top("abc", 1).unwrap();
+        assert!(requests
+            .recv()
+            .unwrap()
+            .starts_with("GET /api/scores/abc?limit=1 HTTP/1.1\r\n"));
+        assert_eq!(top, vec![submission("b", 30.0)]);
+    }
+
+    #[test]
+    fn reports_server_errors() {
+        let (server, _requests) = mock_server(vec![
+            b"HTTP/1.1 403 Forbidden\r\nContent-Length: 13\r\n\r\nbad signature".to_vec(),
+        ]);
+        let signed = submission("a", 10.0).sign(&signing_key("wrong"));
+        match server.submit(&signed) {
+            Err(LeaderboardError::Http { status, body }) => {
+                assert_eq!(status, 403);
+                assert_eq!(body, "bad signature");
+            }
+            other => panic!("expected an HTTP error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn honours_content_length() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\n[]  trailing";
+        let (status, body) = parse_response(response).unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(body, b"[]  ");
+        let short = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n[]";
+        assert!(matches!(
+            parse_response(short),
+            Err(LeaderboardError::Protocol(_))
+        ));
+    }
+
+    #[test]
+    fn signatures_depend_on_the_key_and_contents() {
+        let key = signing_key("secret");
+        let signed = submission("a", 10.0).sign(&key);
+        assert!(signed.verify(&key));
+        assert!(!signed.verify(&signing_key("other")));
+        let mut tampered = signed.clone();
+        tampered.submission.score = 99.0;
+        assert!(!tampered.verify(&key));
+    }
+
+    #[test]
+    fn skips_corrupt_lines_in_the_local_file() {
+        let key = signing_key("secret");
+        let path =
+            std::env::temp_dir().join(format!("leaderboard-corrupt-{}.jsonl", std::process::id()));
+        let leaderboard = LocalFileLeaderboard {
+            path: path.clone(),
+            key,
+        };
+        leaderboard
+            .submit(&submission("a", 10.0).sign(&key))
+            .unwrap();
+        // A line cut short by a crash while writing.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"submission\":{\"player\":\"b\"\n")
+            .unwrap();
+        leaderboard
+            .submit(&submission("c", 30.0).sign(&key))
+            .unwrap();
+
+        let top = leaderboard.top("abc", 5);
+        std::fs::remove_file(&path).unwrap();
+        let players: Vec<_> = top.unwrap().into_iter().map(|entry| entry.player).collect();
+        assert_eq!(players, ["c", "a"]);
+    }
+}
@@ -1,6 +1,53 @@
+mod adaptive;
+mod arena;
+mod audio;
+mod counter_strafe;
+mod crosshair;
+mod editor;
+mod feedback;
 mod fps_gun_plugin;
+mod history;
+mod hot_reload;
+mod hud;
+mod leaderboard;
+mod menu;
+mod net;
+mod rewind;
+mod routine;
+mod scenario;
+mod scope;
+mod scripting;
+mod skills;
+mod spread;
+mod stats;
+mod version;
+mod weapon;
 
+use crate::adaptive::{AdaptiveDifficulty, AdaptiveDifficultyPlugin, Difficulty, DifficultyGoal};
+use crate::arena::{ArenaLayout, ArenaPlugin};
+use crate::audio::{GameAudioPlugin, SoundEffect, SoundPlayer};
+use crate::counter_strafe::CounterStrafePlugin;
+use crate::crosshair::CrosshairPlugin;
+use crate::editor::{EditorPlugin, GameMode};
+use crate::feedback::FeedbackPlugin;
 use crate::fps_gun_plugin::FpsGunPlugin;
+use crate::history::HistoryPlugin;
+use crate::hot_reload::{HotReloadPlugin, ScenarioPath};
+use crate::hud::HudPlugin;
+use crate::leaderboard::LeaderboardPlugin;
+use crate::menu::MenuPlugin;
+use crate::net::{spawns_targets, NetPlugin, NetRole};
+use crate::rewind::LagCompensationPlugin;
+use crate::routine::{ActiveRoutine, RoutinePlugin};
+use crate::scenario::{Ballistics, GridOccupancy, Scenario, SpawnContext, TargetMotion};
+use crate::scope::ScopePlugin;
+use crate::scripting::{ScriptTarget, ScriptingPlugin};
+use crate::skills::SkillsPlugin;
+use crate::spread::ShotConditions;
+use crate::stats::{RestartSession, SessionStats, StatsPlugin};
+use crate::version::VersionPlugin;
+use crate::weapon::{ActiveWeapon, Weapon, WeaponPlugin, WeaponSlot};
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use bevy::render::camera::Exposure;
 use bevy::time::Stopwatch;
@@ -10,9 +57,12 @@ use bevy_rapier3d::prelude::*;
 use rand::distr::Uniform;
 use rand::prelude::*;
 use std::f32::consts::TAU;
-use bevy::audio::{SpatialScale, Volume};
 
 const SPAWN_POINT: Vec3 = Vec3::new(0.0, 1.625, 0.0);
+const SPAWN_PITCH: f32 = -TAU / 12.0;
+const SPAWN_YAW: f32 = TAU * 5.0 / 8.0;
+/// Vertical field of view of the world camera when not zoomed in.
+const CAMERA_FOV: f32 = TAU / 5.0;
 
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
 pub struct FpsControllerSetup;
@@ -21,38 +71,220 @@ pub struct FpsControllerSetup;
 #[reflect(Component, Default)]
 pub struct Target;
 
+/// The grid cell a target occupies, for scenarios that place targets on a grid.
 #[derive(Component)]
-struct PointsDisplay;
+struct GridCell(usize);
 
-#[derive(Default, Resource)]
-struct Points {
-    pub value: i32,
+/// Sent when a shot lands on a target.
+#[derive(Event)]
+pub struct TargetHit {
+    pub entity: Entity,
+    pub position: Vec3,
+    pub killed: bool,
+    /// The shot struck close to the centre of the target as seen by the shooter.
+    pub headshot: bool,
+}
+
+/// Sent for every shot fired, before it is resolved as a hit or a miss.
+#[derive(Event)]
+pub struct ShotFired {
+    pub origin: Vec3,
+    /// Direction of the shot, including spread. Not normalised.
+    pub direction: Vec3,
+    pub slot: WeaponSlot,
+    /// Horizontal speed of the player when the shot went off.
+    pub speed: f32,
+    /// The player was moving slowly enough for movement not to affect the shot.
+    pub accurate: bool,
+}
+
+/// Sent when a shot hits anything other than a target, or nothing at all.
+#[derive(Event)]
+pub struct ShotMissed;
+
+/// Largest angle between the shot and the surface normal that still counts as a headshot.
+const HEADSHOT_ANGLE: f32 = 0.35;
+
+/// Sent when a target leaves play, so that a replacement can be spawned.
+#[derive(Event)]
+pub struct TargetRemoved {
+    pub entity: Entity,
+    pub position: Vec3,
+    pub cell: Option<usize>,
+    /// The target timed out rather than being killed.
+    pub expired: bool,
+    /// The target was spawned by the scenario's script, which replaces its own targets.
+    pub scripted: bool,
 }
 
+/// How long a target has been alive.
 #[derive(Component)]
-struct ShootTracker {
+struct TargetAge {
     stopwatch: Stopwatch,
-    spray_count: usize,
 }
 
+/// Damage a target can still take before it is killed.
+#[derive(Component)]
+struct TargetHealth(f32);
+
+/// A target swinging side to side, for scenarios with strafing targets.
+#[derive(Component)]
+struct StrafeMotion {
+    /// Centre of the swing.
+    anchor: Vec3,
+    axis: Vec3,
+    speed: f32,
+    half_distance: f32,
+    phase: f32,
+}
+
+/// Random source for target placement, seeded so that a target sequence can be shared or replayed.
+#[derive(Resource)]
+pub struct TargetRng {
+    pub seed: u64,
+    rng: StdRng,
+}
+
+impl TargetRng {
+    pub fn new(seed: u64) -> Self {
+        TargetRng {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+/// Where the most recently killed target stood, for spawn strategies chaining off it.
+#[derive(Default, Resource)]
+struct LastKill {
+    pub position: Option<Vec3>,
+}
+
+/// A bullet in flight, for scenarios with projectile ballistics.
+#[derive(Component)]
+struct Projectile {
+    velocity: Vec3,
+    /// Downward acceleration, in metres per second squared.
+    gravity: f32,
+    damage: f32,
+    age: Stopwatch,
+}
+
+/// Bullets still in flight this long are removed and count as missed.
+const PROJECTILE_LIFETIME_SECS: f32 = 3.0;
+
 #[derive(Component)]
 struct BulletImpact {
     stopwatch: Stopwatch,
 }
 
 fn main() {
+    // Usage: aim_trainer [scenario | path/under/assets.scenario.ron] [--routine path/under/assets.routine.ron] [--adaptive accuracy=<0-1> | --adaptive kpm=<kills per minute>] [--host <address> | --join <address>] [--seed <seed>]
+    let mut scenario_name = None;
+    let mut routine_path = None;
+    let mut adaptive = None;
+    let mut net_role = NetRole::Offline;
+    let mut seed = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--host" => {
+                net_role = NetRole::Host {
+                    bind: args
+                        .next()
+                        .unwrap_or_else(|| net::DEFAULT_ADDRESS.to_string()),
+                }
+            }
+            "--join" => {
+                net_role = NetRole::Guest {
+                    server: args
+                        .next()
+                        .unwrap_or_else(|| net::DEFAULT_ADDRESS.to_string()),
+                }
+            }
+            "--seed" => seed = args.next().and_then(|seed| seed.parse().ok()),
+            "--routine" => routine_path = args.next(),
+            "--adaptive" => {
+                adaptive = args.next().and_then(|goal| {
+                    DifficultyGoal::parse(&goal)
+                        .or_else(|| {
+                            eprintln!("Invalid adaptive goal {:?}, ignoring it", goal);
+                            None
+                        })
+                        .map(AdaptiveDifficulty::new)
+                })
+            }
+            _ => scenario_name = Some(arg),
+        }
+    }
+    let mut routine = routine_path.and_then(|path| {
+        ActiveRoutine::load(&path)
+            .inspect_err(|error| eprintln!("Could not load routine {}: {}", path, error))
+            .ok()
+    });
+    if let (Some(routine), Some(adaptive)) = (&mut routine, adaptive) {
+        routine.set_adaptive(adaptive);
+    }
+    let mut scenario = match (&routine, &scenario_name) {
+        (Some(routine), _) => routine.scenario().clone(),
+        (None, Some(name)) => Scenario::find(name).unwrap_or_else(|error| {
+            eprintln!(
+                "Could not load scenario {}: {}, using the default",
+                name, error
+            );
+            Scenario::default()
+        }),
+        (None, None) => Scenario::default(),
+    };
+    if adaptive.is_some() {
+        scenario.adaptive = adaptive;
+    }
+    // Routines change scenario as they go, so only a scenario played on its own is watched.
+    let scenario_path = scenario_name.filter(|name| routine.is_none() && name.ends_with(".ron"));
+    let seed = seed.unwrap_or_else(|| rand::rng().random());
+
     App::new()
         .insert_resource(AmbientLight {
             color: Color::WHITE,
             brightness: 6000.0,
         })
         .insert_resource(ClearColor(Color::srgb(0.83, 0.96, 0.96)))
-        .insert_resource(Points::default()) // Add this line
+        .insert_resource(GridOccupancy::new(scenario.target_placement.grid_cells()))
+        .insert_resource(scenario)
+        .insert_resource(ScenarioPath(scenario_path))
+        .insert_resource(LastKill::default())
+        .insert_resource(TargetRng::new(seed))
+        .insert_resource(net_role)
+        .add_event::<TargetHit>()
+        .add_event::<TargetRemoved>()
+        .add_event::<ShotMissed>()
+        .add_event::<ShotFired>()
         .add_plugins(DefaultPlugins)
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
         //.add_plugins(RapierDebugRenderPlugin::default())
         .add_plugins(FpsControllerPlugin)
+        .add_plugins(GameAudioPlugin)
+        .add_plugins(ArenaPlugin)
         .add_plugins(FpsGunPlugin)
+        .add_plugins(WeaponPlugin)
+        .add_plugins(ScopePlugin)
+        .add_plugins(StatsPlugin)
+        .add_plugins(HudPlugin)
+        .add_plugins(FeedbackPlugin)
+        .add_plugins(CounterStrafePlugin)
+        .add_plugins(NetPlugin)
+        .add_plugins(LagCompensationPlugin::default())
+        .add_plugins(VersionPlugin)
+        .add_plugins(HistoryPlugin)
+        .add_plugins(LeaderboardPlugin)
+        .add_plugins(ScriptingPlugin)
+        .add_plugins(EditorPlugin)
+        .add_plugins(MenuPlugin)
+        .add_plugins(CrosshairPlugin)
+        .add_plugins(HotReloadPlugin)
+        .add_plugins(RoutinePlugin { routine })
+        .add_plugins(AdaptiveDifficultyPlugin)
+        .add_plugins(SkillsPlugin)
         .add_systems(
             Startup,
             (setup, fps_controller_setup.in_set(FpsControllerSetup)),
@@ -62,11 +294,25 @@ fn main() {
             (
                 respawn,
                 manage_cursor,
-                click_targets,
-                update_points_display,
+                (
+                    restart_targets,
+                    spawn_initial_targets.run_if(in_state(GameMode::Playing)),
+                )
+                    .chain()
+                    .run_if(spawns_targets),
+                move_targets.run_if(spawns_targets),
+                reload_weapon,
+                (
+                    click_targets,
+                    advance_projectiles,
+                    expire_targets.run_if(spawns_targets),
+                    replace_targets.run_if(spawns_targets),
+                )
+                    .chain()
+                    .run_if(in_state(GameMode::Playing)),
                 despawn_bullet_impacts,
             ),
-        ) // Add update_points_display system
+        )
         .run();
 }
 
@@ -99,8 +345,8 @@ fn fps_controller_setup(mut commands: Commands) {
             Transform::from_translation(SPAWN_POINT),
             LogicalPlayer,
             FpsControllerInput {
-                pitch: -TAU / 12.0,
-                yaw: TAU * 5.0 / 8.0,
+                pitch: SPAWN_PITCH,
+                yaw: SPAWN_YAW,
                 ..default()
             },
             FpsController {
@@ -114,10 +360,6 @@ fn fps_controller_setup(mut commands: Commands) {
         .insert(fps_gun_plugin::LastPosition {
             last_position: Vec3::ZERO,
         })
-        .insert(ShootTracker {
-            stopwatch: Stopwatch::new(),
-            spray_count: 0,
-        })
         .insert(listener)
         .id();
 
@@ -128,7 +370,7 @@ fn fps_controller_setup(mut commands: Commands) {
             ..default()
         },
         Projection::Perspective(PerspectiveProjection {
-            fov: TAU / 5.0,
+            fov: CAMERA_FOV,
             ..default()
         }),
         Exposure::SUNLIGHT,
@@ -136,13 +378,7 @@ fn fps_controller_setup(mut commands: Commands) {
     ));
 }
 
-fn setup(
-    mut commands: Commands,
-    mut window: Query<&mut Window>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut materials2d: ResMut<Assets<ColorMaterial>>,
-) {
+fn setup(mut commands: Commands, mut window: Query<&mut Window>) {
     let mut window = window.single_mut();
     window.title = String::from("Minimal FPS Controller Example");
 
@@ -162,70 +398,93 @@ fn setup(
             ..default()
         },
     ));
-
-    // Ground collider
-    commands.spawn((
-        Collider::cuboid(20.0, 0.1, 20.0),
-        RigidBody::Fixed,
-        Transform::from_translation(Vec3::new(0.0, -0.5, 0.0)),
-    ));
-    // Ground mesh
-    let ground_material = materials.add(StandardMaterial {
-        base_color: Color::srgb(0.5, 0.5, 0.5),
-        ..Default::default()
-    });
-    commands.spawn((
-        Mesh3d(meshes.add(Cuboid::new(40.0, 0.1, 40.0))),
-        MeshMaterial3d(ground_material.clone()),
-        Transform::from_translation(Vec3::new(0.0, -0.5, 0.0)),
-    ));
-
-    // Wall
-    commands.spawn((
-        Collider::cuboid(5.0, 2.5, 0.5),
-        RigidBody::Fixed,
-        Transform::from_translation(Vec3::new(0.0, 0.0, 10.0)),
-    ));
-    commands.spawn((
-        Mesh3d(meshes.add(Cuboid::new(10.0, 5.0, 1.0))),
-        MeshMaterial3d(ground_material.clone()),
-        Transform::from_translation(Vec3::new(0.0, 0.0, 10.0)),
-    ));
-
-    spawn_random_target(&mut commands, &mut meshes, &mut materials);
-    spawn_random_target(&mut commands, &mut meshes, &mut materials);
-    spawn_random_target(&mut commands, &mut meshes, &mut materials);
-
-    // Crosshair
-    let color = Color::srgb(0.5, 0.7, 1.0);
-    commands.spawn((
-        Mesh2d(meshes.add(Circle::new(2.0))),
-        MeshMaterial2d(materials2d.add(color)),
-        Transform::from_xyz(0.0, 0.0, 0.0),
-    ));
-
-    commands.spawn((
-        // Here we are able to call the `From` method instead of creating a new `TextSection`.
-        // This will use the default font (a minimal subset of FiraMono) and apply the default styling.
-        Text::new("From an &str into a Text with the default font!"),
-        Node {
-            position_type: PositionType::Absolute,
-            bottom: Val::Px(5.0),
-            left: Val::Px(15.0),
-            ..default()
-        },
-        PointsDisplay,
-    ));
 }
 
-fn respawn(mut query: Query<(&mut Transform, &mut Velocity)>) {
+fn respawn(mut query: Query<(&mut Transform, &mut Velocity)>, layout: Res<ArenaLayout>) {
     for (mut transform, mut velocity) in &mut query {
         if transform.translation.y > -50.0 {
             continue;
         }
 
         velocity.linvel = Vec3::ZERO;
-        transform.translation = SPAWN_POINT;
+        transform.translation = layout.player_spawn_point();
+    }
+}
+
+/// Clears the targets and puts the player back at the spawn point. The targets are filled in
+/// again by `spawn_initial_targets`.
+fn restart_targets(
+    mut commands: Commands,
+    mut restarts: EventReader<RestartSession>,
+    targets: Query<Entity, With<Target>>,
+    mut player: Query<
+        (&mut Transform, &mut Velocity, &mut FpsControllerInput),
+        With<LogicalPlayer>,
+    >,
+    mut occupancy: ResMut<GridOccupancy>,
+    mut last_kill: ResMut<LastKill>,
+    scenario: Res<Scenario>,
+    layout: Res<ArenaLayout>,
+) {
+    if restarts.read().last().is_none() {
+        return;
+    }
+    for entity in &targets {
+        commands.entity(entity).despawn_recursive();
+    }
+    *occupancy = GridOccupancy::new(scenario.target_placement.grid_cells());
+    *last_kill = LastKill::default();
+
+    let (yaw, pitch, _) = layout.player_view().rotation.to_euler(EulerRot::YXZ);
+    for (mut transform, mut velocity, mut input) in &mut player {
+        transform.translation = layout.player_spawn_point();
+        velocity.linvel = Vec3::ZERO;
+        input.yaw = yaw;
+        input.pitch = pitch;
+    }
+}
+
+/// Fills the arena with the scenario's targets once its spawn points are known, and again
+/// after each restart.
+fn spawn_initial_targets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    scenario: Res<Scenario>,
+    mut occupancy: ResMut<GridOccupancy>,
+    mut target_rng: ResMut<TargetRng>,
+    layout: Res<ArenaLayout>,
+    difficulty: Res<Difficulty>,
+    mut restarts: EventReader<RestartSession>,
+    mut spawned: Local<bool>,
+) {
+    if restarts.read().last().is_some() {
+        *spawned = false;
+    }
+    if *spawned || !layout.ready {
+        return;
+    }
+    *spawned = true;
+
+    // The controller may not have positioned the camera yet, so aim from the spawn point.
+    let view = layout.player_view();
+    let context = SpawnContext {
+        view: &view,
+        last_kill: None,
+        vacated_cell: None,
+        spawn_volumes: &layout.target_volumes,
+    };
+    for _ in 0..scenario.target_count {
+        spawn_random_target(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &scenario,
+            &mut occupancy,
+            &mut target_rng,
+            &context,
+            &difficulty,
+        );
     }
 }
 
@@ -253,141 +512,287 @@ fn manage_cursor(
     }
 }
 
-const SPRAY_DIRECTIONS: [Vec3; 12] = [
-    Vec3::new(0.0, 0.0, 0.0),
-    Vec3::new(-0.01, 0.025, 0.0),
-    Vec3::new(-0.02, 0.05, 0.0),
-    Vec3::new(-0.03, 0.055, 0.0),
-    Vec3::new(-0.032, 0.065, 0.0),
-    Vec3::new(-0.034, 0.075, 0.0),
-    Vec3::new(-0.038, 0.08, 0.0),
-    Vec3::new(-0.042, 0.082, 0.0),
-    Vec3::new(-0.046, 0.085, 0.0),
-    Vec3::new(-0.042, 0.087, 0.0),
-    Vec3::new(-0.039, 0.090, 0.0),
-    Vec3::new(-0.038, 0.093, 0.0),
-];
+/// Applies the outcome of a shot: impact marker, damage, stats and events.
+#[derive(SystemParam)]
+struct ShotResolver<'w, 's> {
+    commands: Commands<'w, 's>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    materials: ResMut<'w, Assets<StandardMaterial>>,
+    sounds: SoundPlayer<'w, 's>,
+    targets: Query<
+        'w,
+        's,
+        (
+            &'static Transform,
+            Option<&'static GridCell>,
+            &'static mut TargetHealth,
+            &'static TargetAge,
+            Has<ScriptTarget>,
+        ),
+        With<Target>,
+    >,
+    stats: ResMut<'w, SessionStats>,
+    target_hits: EventWriter<'w, TargetHit>,
+    target_removed: EventWriter<'w, TargetRemoved>,
+    shots_missed: EventWriter<'w, ShotMissed>,
+}
+
+/// What a shot did to the target it struck.
+#[derive(Clone, Copy, Debug)]
+struct TargetDamage {
+    dealt: f32,
+    killed: bool,
+    headshot: bool,
+    /// How long the target had been alive.
+    target_age_secs: f32,
+}
+
+impl ShotResolver<'_, '_> {
+    /// Resolves the player's own shot travelling along `direction` that struck `hit`, or nothing.
+    fn resolve(&mut self, hit: Option<(Entity, Vec3)>, direction: Vec3, damage: f32) {
+        let Some((entity, hit_point)) = hit else {
+            self.stats.record_shot(false);
+            self.shots_missed.send(ShotMissed);
+            return;
+        };
+
+        println!("Hit entity {:?} at {:?}", entity, hit_point);
+        self.commands.spawn((
+            BulletImpact {
+                stopwatch: Stopwatch::new(),
+            },
+            Transform::from_translation(hit_point),
+            Mesh3d(self.meshes.add(Sphere::new(0.1))),
+            MeshMaterial3d(self.materials.add(StandardMaterial {
+                base_color: Color::srgb(1.0, 0.0, 0.0),
+                ..Default::default()
+            })),
+        ));
+
+        // Handle the hit.
+        if let Some(result) = self.damage_target(entity, hit_point, direction, damage) {
+            println!("Hit target entity {:?}", entity);
+            self.stats.record_shot(true);
+            self.stats.record_damage(result.dealt);
+            self.target_hits.send(TargetHit {
+                entity,
+                position: hit_point,
+                killed: result.killed,
+                headshot: result.headshot,
+            });
+            if result.killed {
+                self.stats.record_kill(result.target_age_secs);
+            }
+        } else {
+            let mut rng = rand::rng();
+            let pitch_range = Uniform::new(-0.12f32, 0.12).unwrap();
+            self.sounds.play(
+                &mut self.commands,
+                SoundEffect::Impact,
+                0.35,
+                1.0 + rng.sample(pitch_range),
+                Some(hit_point),
+            );
+            self.stats.record_shot(false);
+            self.shots_missed.send(ShotMissed);
+        }
+    }
+
+    /// Deals `damage` to `entity` if it is a living target, removing it when killed.
+    /// Stats and feedback are left to the caller, as the shot may not be the local player's.
+    fn damage_target(
+        &mut self,
+        entity: Entity,
+        hit_point: Vec3,
+        direction: Vec3,
+        damage: f32,
+    ) -> Option<TargetDamage> {
+        let (target_transform, grid_cell, mut health, age, scripted) =
+            self.targets.get_mut(entity).ok()?;
+        // A target killed earlier this frame is only despawned at the end of it, and must not
+        // be killed again.
+        let was_alive = health.0 > 0.0;
+        if !was_alive {
+            return None;
+        }
+        let dealt = damage.min(health.0);
+        health.0 -= damage;
+        let killed = health.0 <= 0.0;
+        let normal = (hit_point - target_transform.translation).normalize_or_zero();
+        let headshot = normal.angle_between(-direction) < HEADSHOT_ANGLE;
+        if killed {
+            // Remove the target
+            self.commands.entity(entity).despawn_recursive();
+            self.target_removed.send(TargetRemoved {
+                entity,
+                position: target_transform.translation,
+                cell: grid_cell.map(|cell| cell.0),
+                expired: false,
+                scripted,
+            });
+        }
+        Some(TargetDamage {
+            dealt,
+            killed,
+            headshot,
+            target_age_secs: age.stopwatch.elapsed_secs(),
+        })
+    }
+}
 
 fn click_targets(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    asset_server: Res<AssetServer>,
+    mut resolver: ShotResolver,
     rapier_context: ReadRapierContext,
-    player_query: Query<Entity, With<LogicalPlayer>>,
+    player_query: Query<
+        (Entity, &Velocity, &FpsController, &FpsControllerInput),
+        With<LogicalPlayer>,
+    >,
     camera: Query<&Transform, With<RenderPlayer>>,
     buttons: Res<ButtonInput<MouseButton>>,
-    targets: Query<Entity, With<Target>>,
-    mut points: ResMut<Points>,
-    mut gun_animation_state: Query<&mut fps_gun_plugin::GunAnimationState>,
-    mut shoot_stopwatch: Query<&mut ShootTracker>,
-    time: Res<Time>,
+    mut shots_fired: EventWriter<ShotFired>,
+    mut active_weapon: Query<
+        (&mut Weapon, &mut fps_gun_plugin::GunAnimationState),
+        With<ActiveWeapon>,
+    >,
+    scenario: Res<Scenario>,
+    net_role: Res<NetRole>,
 ) {
-    let player_handle = player_query.single();
-    let mut shoot_tracker = shoot_stopwatch
-        .get_mut(player_handle)
-        .expect("LogicalPlayer also needs a ShootTracker");
+    let (player_handle, player_velocity, controller, controller_input) = player_query.single();
+    // No weapon is active while switching.
+    let Ok((mut weapon, mut gun_animation_state)) = active_weapon.get_single_mut() else {
+        return;
+    };
 
-    shoot_tracker.stopwatch.tick(time.delta());
+    let can_fire = weapon.ammo > 0 && weapon.reload.is_none();
+    let trigger = if weapon.stats.automatic {
+        buttons.pressed(MouseButton::Left)
+    } else {
+        buttons.just_pressed(MouseButton::Left)
+    };
+    gun_animation_state.shooting = buttons.pressed(MouseButton::Left) && can_fire;
+    if trigger && weapon.stopwatch.elapsed_secs() > weapon.stats.fire_interval_secs && can_fire {
+        let camera_transform = camera.single();
+        let ray_pos = camera_transform.translation;
 
-    if let Ok(mut gun_animation_state) = gun_animation_state.get_single_mut() {
-        if buttons.pressed(MouseButton::Left) {
-            gun_animation_state.shooting = true;
-        } else {
-            gun_animation_state.shooting = false;
-        }
-    }
-    if buttons.pressed(MouseButton::Left) {
-        if shoot_tracker.stopwatch.elapsed_secs() > 0.1 {
-            let rapier_context = rapier_context.single();
-            let camera_transform = camera.single();
-            let ray_pos = camera_transform.translation;
-            let mut spray: Vec3;
-
-            // Spray while holding left mouse button
-            if shoot_tracker.spray_count >= SPRAY_DIRECTIONS.len() {
-                let mut rng = rand::rng();
-                let range = Uniform::new(-0.065f32, 0.065).unwrap();
-                spray = Vec3::new(rng.sample(range), rng.sample(range), 0.0);
-            } else {
-                spray = SPRAY_DIRECTIONS[shoot_tracker.spray_count];
-            }
+        // Recoil and spread build up with each shot and recover between shots
+        let speed = player_velocity.linvel.xz().length();
+        let conditions = ShotConditions {
+            secs_since_last_shot: weapon.stopwatch.elapsed_secs(),
+            speed,
+            airborne: controller.ground_tick == 0,
+            crouched: controller_input.crouch,
+            scoped: weapon.scoped,
+        };
+        let (shot_spread, spread_state) =
+            weapon
+                .stats
+                .spread
+                .shoot(&weapon.stats.spray_pattern, weapon.spread, &conditions);
+        weapon.spread = spread_state;
+        weapon.ammo -= 1;
+        let mut rng = rand::rng();
+        let spray = shot_spread.sample(&mut rng);
+        let ray_dir = camera_transform.forward().as_vec3() + camera_transform.rotation * spray;
+        shots_fired.send(ShotFired {
+            origin: ray_pos,
+            direction: ray_dir,
+            slot: weapon.slot,
+            speed,
+            accurate: weapon.stats.spread.movement_spread(speed) == 0.0,
+        });
 
-            // Spray while walking
-            if let Ok(gun_animation_state) = gun_animation_state.get_single() {
-                if gun_animation_state.walking {
-                    let mut rng = rand::rng();
-                    let range = Uniform::new(-0.1f32, 0.1).unwrap();
-                    spray += Vec3::new(rng.sample(range), rng.sample(range), 0.0);
-                }
-            }
+        let pitch_range = Uniform::new(-0.12f32, 0.12).unwrap();
 
-            // Increment the spray count
-            shoot_tracker.spray_count += 1;
+        resolver.sounds.play(
+            &mut resolver.commands,
+            SoundEffect::RifleFire,
+            weapon.stats.fire_volume,
+            weapon.stats.fire_pitch + rng.sample(pitch_range),
+            Some(ray_pos),
+        );
 
-            let mut rng = rand::rng();
-            let pitch_range = Uniform::new(-0.12f32, 0.12).unwrap();
+        match scenario.ballistics {
+            // A guest's shots are resolved by the host.
+            _ if net_role.is_guest() => {}
+            Ballistics::Hitscan => {
+                let rapier_context = rapier_context.single();
+                let max_toi: bevy_rapier3d::math::Real = 100.0;
+                let solid = true;
+                let filter = QueryFilter::new()
+                    .exclude_sensors()
+                    .exclude_rigid_body(player_handle);
 
-            commands.spawn((
-                Transform::from_translation(ray_pos),
-                AudioPlayer::new(
-                    asset_server.load("sounds/weapons-rifle-assault-rifle-fire-01.ogg"),
-                ),
-                PlaybackSettings::DESPAWN.with_spatial(true).with_speed(1.1 + rng.sample(pitch_range)).with_volume(Volume::new(0.3)),
-            ));
-
-            let ray_dir = camera_transform.forward().as_vec3() + camera_transform.rotation * spray;
-            let max_toi: bevy_rapier3d::math::Real = 100.0;
-            let solid = true;
-            let filter = QueryFilter::new()
-                .exclude_sensors()
-                .exclude_rigid_body(player_handle);
-
-            if let Some((entity, toi)) =
-                rapier_context.cast_ray(ray_pos, ray_dir, max_toi, solid, filter)
-            {
-                let hit_point = ray_pos + ray_dir * Vec3::splat(toi.into());
-                println!("Hit entity {:?} at {:?}", entity, hit_point);
-                commands.spawn((
-                    BulletImpact {
-                        stopwatch: Stopwatch::new(),
+                let hit = rapier_context
+                    .cast_ray(ray_pos, ray_dir, max_toi, solid, filter)
+                    .map(|(entity, toi)| (entity, ray_pos + ray_dir * Vec3::splat(toi.into())));
+                resolver.resolve(hit, ray_dir, weapon.stats.damage);
+            }
+            Ballistics::Projectile { speed, gravity } => {
+                let mesh = resolver.meshes.add(Sphere::new(0.03));
+                let material = resolver.materials.add(StandardMaterial {
+                    base_color: Color::srgb(1.0, 0.9, 0.4),
+                    unlit: true,
+                    ..Default::default()
+                });
+                resolver.commands.spawn((
+                    Projectile {
+                        velocity: ray_dir.normalize() * speed,
+                        gravity,
+                        damage: weapon.stats.damage,
+                        age: Stopwatch::new(),
                     },
-                    Transform::from_translation(hit_point),
-                    Mesh3d(meshes.add(Sphere::new(0.1))),
-                    MeshMaterial3d(materials.add(StandardMaterial {
-                        base_color: Color::srgb(1.0, 0.0, 0.0),
-                        ..Default::default()
-                    })),
+                    Transform::from_translation(ray_pos),
+                    Mesh3d(mesh),
+                    MeshMaterial3d(material),
                 ));
+            }
+        }
 
-                commands.spawn((
-                    Transform::from_translation(hit_point),
-                    AudioPlayer::new(
-                        asset_server.load("sounds/weapons-shield-metal-impact-ring-02.ogg"),
-                    ),
-                    PlaybackSettings::DESPAWN.with_spatial(true).with_spatial_scale(SpatialScale::new(0.2)).with_volume(Volume::new(0.35)).with_speed(1.0 + rng.sample(pitch_range)),
-                ));
+        weapon.stopwatch.reset();
+    }
+}
 
-                // Handle the hit.
-                if let Ok(target_entity) = targets.get(entity) {
-                    println!("Hit target entity {:?}", target_entity);
-                    // Remove the target
-                    commands.entity(entity).despawn_recursive();
-                    // Spawn a new target
-                    spawn_random_target(&mut commands, &mut meshes, &mut materials);
-                    // Increment points
-                    points.value += 1;
-                } else {
-                    points.value -= 1;
-                }
-            } else {
-                points.value -= 1;
-            }
+/// Moves bullets in flight, sweeping a ray over each frame's travel so that nothing is tunnelled
+/// through. Hits are resolved against wherever targets are at that moment.
+fn advance_projectiles(
+    mut resolver: ShotResolver,
+    mut projectiles: Query<(Entity, &mut Projectile, &mut Transform), Without<Target>>,
+    player_query: Query<Entity, With<LogicalPlayer>>,
+    rapier_context: ReadRapierContext,
+    time: Res<Time>,
+) {
+    let rapier_context = rapier_context.single();
+    let player_handle = player_query.single();
+    let filter = QueryFilter::new()
+        .exclude_sensors()
+        .exclude_rigid_body(player_handle);
+    let delta = time.delta_secs();
 
-            shoot_tracker.stopwatch.reset();
+    for (entity, mut projectile, mut transform) in &mut projectiles {
+        projectile.age.tick(time.delta());
+        let start = transform.translation;
+        let travel = projectile.velocity * delta;
+
+        // With the travel as the ray direction, a time of impact of 1 is the end of this frame.
+        if let Some((hit_entity, toi)) = rapier_context.cast_ray(start, travel, 1.0, true, filter) {
+            let hit_point = start + travel * toi;
+            resolver.commands.entity(entity).despawn();
+            resolver.resolve(
+                Some((hit_entity, hit_point)),
+                projectile.velocity,
+                projectile.damage,
+            );
+            continue;
         }
-    } else {
-        shoot_tracker.spray_count = 0;
+
+        if projectile.age.elapsed_secs() > PROJECTILE_LIFETIME_SECS {
+            resolver.commands.entity(entity).despawn();
+            resolver.resolve(None, projectile.velocity, projectile.damage);
+            continue;
+        }
+
+        transform.translation += travel;
+        projectile.velocity.y -= projectile.gravity * delta;
     }
 }
 
@@ -395,41 +800,219 @@ fn spawn_random_target(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<StandardMaterial>>,
+    scenario: &Scenario,
+    occupancy: &mut GridOccupancy,
+    target_rng: &mut TargetRng,
+    context: &SpawnContext,
+    difficulty: &Difficulty,
 ) {
-    let mut rng = rand::rng();
-    let range_x = Uniform::new(-4.0f32, 4.0).unwrap();
-    let range_y = Uniform::new(2.0f32, 5.0).unwrap();
-    let range_z = Uniform::new(1.0f32, 2.0).unwrap();
-    let range_size = Uniform::new(0.3f32, 0.8).unwrap();
+    let rng = &mut target_rng.rng;
     let range_color = Uniform::new(0.1f32, 1.0).unwrap();
-    let x = rng.sample(range_x);
-    let y = rng.sample(range_y);
-    let z = rng.sample(range_z);
-    let size = rng.sample(range_size);
+    let Some(spawn) = scenario.sample_target(context, occupancy, rng) else {
+        warn!("No free grid cell for a new target");
+        return;
+    };
     let color = Color::srgb(
         rng.sample(range_color),
         rng.sample(range_color),
         rng.sample(range_color),
     );
 
+    let target = spawn_target(
+        commands,
+        meshes,
+        materials,
+        spawn.position,
+        spawn.radius * difficulty.size_scale(),
+        color,
+    );
+    if let Some(cell) = spawn.cell {
+        commands.entity(target).insert(GridCell(cell));
+    }
+    commands.entity(target).insert((
+        TargetAge {
+            stopwatch: Stopwatch::new(),
+        },
+        TargetHealth(scenario.target_health),
+    ));
+    if let TargetMotion::Strafe { speed, distance } = scenario.target_motion {
+        // Level with the ground, across the line of sight.
+        let axis = (spawn.position - context.view.translation)
+            .cross(Vec3::Y)
+            .normalize_or(Vec3::X);
+        commands.entity(target).insert(StrafeMotion {
+            anchor: spawn.position,
+            axis,
+            speed,
+            half_distance: distance / 2.0,
+            phase: 0.0,
+        });
+    }
+}
+
+fn move_targets(
+    mut targets: Query<(&mut Transform, &mut StrafeMotion)>,
+    difficulty: Res<Difficulty>,
+    time: Res<Time>,
+) {
+    for (mut transform, mut motion) in &mut targets {
+        if motion.half_distance <= 0.0 {
+            continue;
+        }
+        // A sine swing peaks at its amplitude times its angular frequency.
+        let speed = motion.speed * difficulty.speed_scale();
+        motion.phase += speed / motion.half_distance * time.delta_secs();
+        transform.translation =
+            motion.anchor + motion.axis * motion.half_distance * motion.phase.sin();
+    }
+}
+
+/// Spawns a target's body and mesh. Health and age are up to the caller.
+fn spawn_target(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    position: Vec3,
+    radius: f32,
+    color: Color,
+) -> Entity {
     let target_material = materials.add(StandardMaterial {
         base_color: color,
         ..Default::default()
     });
 
-    commands.spawn((
-        Collider::ball(size),
-        RigidBody::Fixed,
-        Transform::from_translation(Vec3::new(x, y, z)),
-        Target,
-        Mesh3d(meshes.add(Sphere::new(size))),
-        MeshMaterial3d(target_material),
-    ));
+    commands
+        .spawn((
+            Collider::ball(radius),
+            RigidBody::Fixed,
+            Transform::from_translation(position),
+            Target,
+            Mesh3d(meshes.add(Sphere::new(radius))),
+            MeshMaterial3d(target_material),
+        ))
+        .id()
+}
+
+fn expire_targets(
+    mut commands: Commands,
+    mut targets: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut TargetAge,
+            Option<&GridCell>,
+            Has<ScriptTarget>,
+        ),
+        With<Target>,
+    >,
+    mut stats: ResMut<SessionStats>,
+    mut target_removed: EventWriter<TargetRemoved>,
+    scenario: Res<Scenario>,
+    difficulty: Res<Difficulty>,
+    time: Res<Time>,
+) {
+    for (entity, mut transform, mut age, grid_cell, scripted) in &mut targets {
+        age.stopwatch.tick(time.delta());
+        let Some(timeout) = &scenario.target_timeout else {
+            continue;
+        };
+        let lifetime_secs = timeout.lifetime_secs * difficulty.lifetime_scale();
+        let remaining = 1.0 - age.stopwatch.elapsed_secs() / lifetime_secs;
+        if remaining > 0.0 {
+            if timeout.shrink {
+                transform.scale = Vec3::splat(remaining);
+            }
+            continue;
+        }
+
+        commands.entity(entity).despawn_recursive();
+        target_removed.send(TargetRemoved {
+            entity,
+            position: transform.translation,
+            cell: grid_cell.map(|cell| cell.0),
+            expired: true,
+            scripted,
+        });
+        stats.record_expired();
+    }
+}
+
+fn replace_targets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut target_removed: EventReader<TargetRemoved>,
+    camera: Query<&Transform, With<RenderPlayer>>,
+    mut last_kill: ResMut<LastKill>,
+    scenario: Res<Scenario>,
+    mut occupancy: ResMut<GridOccupancy>,
+    mut target_rng: ResMut<TargetRng>,
+    layout: Res<ArenaLayout>,
+    difficulty: Res<Difficulty>,
+) {
+    let Ok(camera_transform) = camera.get_single() else {
+        return;
+    };
+    for removed in target_removed.read() {
+        if !removed.expired {
+            last_kill.position = Some(removed.position);
+        }
+        // Spawn the replacement first, then free the cell so it can't reappear in place
+        if scenario.target_count > 0 && !removed.scripted {
+            let context = SpawnContext {
+                view: camera_transform,
+                last_kill: last_kill.position,
+                vacated_cell: removed.cell,
+                spawn_volumes: &layout.target_volumes,
+            };
+            spawn_random_target(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                &scenario,
+                &mut occupancy,
+                &mut target_rng,
+                &context,
+                &difficulty,
+            );
+        }
+        if let Some(cell) = removed.cell {
+            occupancy.release(cell);
+        }
+    }
 }
 
-fn update_points_display(points: Res<Points>, mut query: Query<&mut Text, With<PointsDisplay>>) {
-    for mut text in &mut query {
-        text.0 = format!("Points: {}", points.value);
+fn reload_weapon(
+    key: Res<ButtonInput<KeyCode>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut active_weapon: Query<
+        (&mut Weapon, &mut fps_gun_plugin::GunAnimationState),
+        With<ActiveWeapon>,
+    >,
+    time: Res<Time>,
+) {
+    for (mut weapon, mut gun_animation_state) in &mut active_weapon {
+        let magazine_size = weapon.stats.magazine_size;
+        let wants_reload = (key.just_pressed(KeyCode::KeyR) && weapon.ammo < magazine_size)
+            || (buttons.just_pressed(MouseButton::Left) && weapon.ammo == 0);
+        if wants_reload && weapon.reload.is_none() {
+            weapon.reload = Some(Stopwatch::new());
+        }
+
+        let reload_secs = weapon.stats.reload_secs;
+        let finished = match weapon.reload.as_mut() {
+            Some(reload) => {
+                reload.tick(time.delta());
+                reload.elapsed_secs() >= reload_secs
+            }
+            None => false,
+        };
+        if finished {
+            weapon.ammo = magazine_size;
+            weapon.reload = None;
+        }
+
+        gun_animation_state.reloading = weapon.reload.is_some();
     }
 }
 
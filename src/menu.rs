@@ -0,0 +1,104 @@
+use crate::editor::GameMode;
+use crate::stats::RestartSession;
+use bevy::prelude::*;
+use bevy::window::CursorGrabMode;
+
+/// The menu shown while the cursor is free, at start and after Escape. Clicking anywhere,
+/// including on a button, captures the cursor again and hides it.
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_menu);
+        app.add_systems(Update, (show_menu, press_menu_buttons));
+    }
+}
+
+#[derive(Component)]
+struct Menu;
+
+#[derive(Component, Clone, Copy)]
+enum MenuButton {
+    Play,
+    Edit,
+    Restart,
+}
+
+impl MenuButton {
+    fn label(&self) -> &'static str {
+        match self {
+            MenuButton::Play => "Play",
+            MenuButton::Edit => "Scenario editor",
+            MenuButton::Restart => "Restart",
+        }
+    }
+}
+
+fn spawn_menu(mut commands: Commands) {
+    commands
+        .spawn((
+            Menu,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(10.0),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            for button in [MenuButton::Play, MenuButton::Edit, MenuButton::Restart] {
+                parent
+                    .spawn((
+                        button,
+                        Button,
+                        Node {
+                            width: Val::Px(240.0),
+                            padding: UiRect::all(Val::Px(10.0)),
+                            justify_content: JustifyContent::Center,
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+                        BorderRadius::all(Val::Px(6.0)),
+                    ))
+                    .with_child(Text::new(button.label()));
+            }
+        });
+}
+
+fn show_menu(window: Query<&Window>, mut menu: Query<&mut Visibility, With<Menu>>) {
+    let free = window
+        .iter()
+        .any(|window| window.cursor_options.grab_mode == CursorGrabMode::None);
+    for mut visibility in &mut menu {
+        visibility.set_if_neq(if free {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
+
+fn press_menu_buttons(
+    buttons: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mode: Res<State<GameMode>>,
+    mut next_mode: ResMut<NextState<GameMode>>,
+    mut restarts: EventWriter<RestartSession>,
+) {
+    for (interaction, button) in &buttons {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            MenuButton::Play => next_mode.set(GameMode::Playing),
+            MenuButton::Edit => next_mode.set(GameMode::Editing),
+            MenuButton::Restart if *mode.get() == GameMode::Playing => {
+                restarts.send(RestartSession);
+            }
+            MenuButton::Restart => {}
+        }
+    }
+}
@@ -0,0 +1,1162 @@
+use crate::rewind::TargetHistory;
+use crate::scenario::{Ballistics, Scenario};
+use crate::stats::SessionStats;
+use crate::weapon::{Loadout, WeaponSlot};
+use crate::{
+    spawn_target, ShotFired, ShotMissed, ShotResolver, Target, TargetHit, TargetRng,
+    PROJECTILE_LIFETIME_SECS,
+};
+use bevy::prelude::*;
+use bevy::time::Stopwatch;
+use bevy::utils::HashMap;
+use bevy_fps_controller::controller::{LogicalPlayer, RenderPlayer};
+use bevy_rapier3d::prelude::*;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+
+/// Used by `--host` and `--join` when no address is given.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7777";
+/// How often each side reports where its player is, and the host where moving targets are.
+const POSITION_INTERVAL_SECS: f32 = 0.05;
+/// How often a guest repeats its join request until the host answers.
+const HELLO_INTERVAL_SECS: f32 = 1.0;
+/// Furthest a guest's shot may start from its last reported position.
+const MAX_ORIGIN_ERROR: f32 = 1.5;
+/// Shots from a guest closer together than this are rejected.
+const MIN_SHOT_INTERVAL_SECS: f32 = 0.05;
+/// How far a guest's shot is traced.
+const MAX_SHOT_DISTANCE: f32 = 100.0;
+/// Longest step a guest's bullet is moved in, when catching up with the flight the guest has
+/// already seen.
+const PROJECTILE_STEP_SECS: f32 = 1.0 / 60.0;
+/// How often the host repeats which targets exist, so that lost spawns and removals are
+/// made good.
+const SYNC_INTERVAL_SECS: f32 = 1.0;
+
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DuelScore::default());
+        app.insert_resource(OpponentPosition::default());
+        app.insert_resource(HostClock::default());
+        app.add_systems(Startup, open_socket);
+        app.add_systems(
+            Update,
+            (
+                (
+                    host_receive,
+                    host_advance_guest_projectiles,
+                    host_announce_targets,
+                    host_send_target_moves,
+                    host_sync_targets,
+                    host_count_kills,
+                )
+                    .chain()
+                    .run_if(is_host),
+                (guest_connect, guest_receive, guest_send_shots)
+                    .chain()
+                    .run_if(is_guest),
+                (send_position, show_opponent, update_score_text).run_if(is_networked),
+            ),
+        );
+    }
+}
+
+/// Whether this instance plays alone, hosts a duel or joins one.
+#[derive(Resource, Clone, Debug, Default, PartialEq)]
+pub enum NetRole {
+    #[default]
+    Offline,
+    /// Owns the targets and validates the guest's shots.
+    Host { bind: String },
+    /// Mirrors the host's targets and sends its shots to the host.
+    Guest { server: String },
+}
+
+impl NetRole {
+    pub fn is_guest(&self) -> bool {
+        matches!(self, NetRole::Guest { .. })
+    }
+}
+
+/// Run condition for systems that create and remove targets, which only the host or a solo
+/// player does.
+pub fn spawns_targets(role: Res<NetRole>) -> bool {
+    !role.is_guest()
+}
+
+pub fn is_host(role: Res<NetRole>) -> bool {
+    matches!(*role, NetRole::Host { .. })
+}
+
+fn is_guest(role: Res<NetRole>) -> bool {
+    role.is_guest()
+}
+
+fn is_networked(role: Res<NetRole>) -> bool {
+    *role != NetRole::Offline
+}
+
+/// Kills made by each side of a duel.
+#[derive(Resource, Default, Debug)]
+pub struct DuelScore {
+    pub host: u32,
+    pub guest: u32,
+}
+
+/// A message between host and guest. Each is sent as one datagram holding a line of text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    /// The guest asks to join.
+    Hello,
+    /// The host accepts, sharing the seed of its target sequence.
+    Welcome {
+        seed: u64,
+    },
+    /// Where the sender's player is, and the time on the sender's clock.
+    Position {
+        position: Vec3,
+        time: f32,
+    },
+    /// A target appeared.
+    Spawn {
+        id: u32,
+        position: Vec3,
+        radius: f32,
+    },
+    /// A target moved or shrank.
+    Move {
+        id: u32,
+        position: Vec3,
+        scale: f32,
+    },
+    /// A target was killed or expired.
+    Remove {
+        id: u32,
+    },
+    /// Every target that exists. Spawns and removals are not acknowledged, so the guest drops
+    /// any mirrored target missing from this list, and the host sends a spawn for each after it.
+    Sync {
+        ids: Vec<u32>,
+    },
+    /// The guest fired along a ray, at `time` on the host's clock as estimated by the guest.
+    Shot {
+        shot: u32,
+        time: f32,
+        slot: WeaponSlot,
+        origin: Vec3,
+        direction: Vec3,
+    },
+    /// The host's verdict on one of the guest's shots. `hit` is `None` for a miss.
+    ShotResult {
+        shot: u32,
+        hit: Option<ShotHit>,
+    },
+    Score {
+        host: u32,
+        guest: u32,
+    },
+}
+
+/// A guest shot the host found to have struck a target.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShotHit {
+    pub target: u32,
+    pub position: Vec3,
+    pub damage: f32,
+    pub killed: bool,
+    pub headshot: bool,
+    pub target_age_secs: f32,
+}
+
+impl Message {
+    pub fn encode(&self) -> String {
+        match self {
+            Message::Hello => String::from("HELLO"),
+            Message::Welcome { seed } => format!("WELCOME {}", seed),
+            Message::Position { position, time } => {
+                format!("POS {} {}", encode_vec3(*position), time)
+            }
+            Message::Spawn {
+                id,
+                position,
+                radius,
+            } => format!("SPAWN {} {} {}", id, encode_vec3(*position), radius),
+            Message::Move {
+                id,
+                position,
+                scale,
+            } => format!("MOVE {} {} {}", id, encode_vec3(*position), scale),
+            Message::Remove { id } => format!("REMOVE {}", id),
+            Message::Sync { ids } => {
+                let mut text = String::from("SYNC");
+                for id in ids {
+                    text.push_str(&format!(" {}", id));
+                }
+                text
+            }
+            Message::Shot {
+                shot,
+                time,
+                slot,
+                origin,
+                direction,
+            } => format!(
+                "SHOT {} {} {} {} {}",
+                shot,
+                time,
+                slot.index(),
+                encode_vec3(*origin),
+                encode_vec3(*direction)
+            ),
+            Message::ShotResult { shot, hit: None } => format!("RESULT {} MISS", shot),
+            Message::ShotResult {
+                shot,
+                hit: Some(hit),
+            } => format!(
+                "RESULT {} HIT {} {} {} {} {} {}",
+                shot,
+                hit.target,
+                encode_vec3(hit.position),
+                hit.damage,
+                hit.killed as u8,
+                hit.headshot as u8,
+                hit.target_age_secs
+            ),
+            Message::Score { host, guest } => format!("SCORE {} {}", host, guest),
+        }
+    }
+
+    /// Parses a message, or returns `None` if it is malformed.
+    pub fn decode(text: &str) -> Option<Message> {
+        let mut tokens = text.split_whitespace();
+        let message = match tokens.next()? {
+            "HELLO" => Message::Hello,
+            "WELCOME" => Message::Welcome {
+                seed: tokens.next()?.parse().ok()?,
+            },
+            "POS" => Message::Position {
+                position: decode_vec3(&mut tokens)?,
+                time: tokens.next()?.parse().ok()?,
+            },
+            "SPAWN" => Message::Spawn {
+                id: tokens.next()?.parse().ok()?,
+                position: decode_vec3(&mut tokens)?,
+                radius: tokens.next()?.parse().ok()?,
+            },
+            "MOVE" => Message::Move {
+                id: tokens.next()?.parse().ok()?,
+                position: decode_vec3(&mut tokens)?,
+                scale: tokens.next()?.parse().ok()?,
+            },
+            "REMOVE" => Message::Remove {
+                id: tokens.next()?.parse().ok()?,
+            },
+            "SYNC" => Message::Sync {
+                ids: tokens
+                    .map(|token| token.parse().ok())
+                    .collect::<Option<_>>()?,
+            },
+            "SHOT" => Message::Shot {
+                shot: tokens.next()?.parse().ok()?,
+                time: tokens.next()?.parse().ok()?,
+                slot: WeaponSlot::from_index(tokens.next()?.parse().ok()?)?,
+                origin: decode_vec3(&mut tokens)?,
+                direction: decode_vec3(&mut tokens)?,
+            },
+            "RESULT" => {
+                let shot = tokens.next()?.parse().ok()?;
+                let hit = match tokens.next()? {
+                    "MISS" => None,
+                    "HIT" => Some(ShotHit {
+                        target: tokens.next()?.parse().ok()?,
+                        position: decode_vec3(&mut tokens)?,
+                        damage: tokens.next()?.parse().ok()?,
+                        killed: tokens.next()? == "1",
+                        headshot: tokens.next()? == "1",
+                        target_age_secs: tokens.next()?.parse().ok()?,
+                    }),
+                    _ => return None,
+                };
+                Message::ShotResult { shot, hit }
+            }
+            "SCORE" => Message::Score {
+                host: tokens.next()?.parse().ok()?,
+                guest: tokens.next()?.parse().ok()?,
+            },
+            _ => return None,
+        };
+        Some(message)
+    }
+}
+
+fn encode_vec3(value: Vec3) -> String {
+    format!("{} {} {}", value.x, value.y, value.z)
+}
+
+fn decode_vec3<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Option<Vec3> {
+    Some(Vec3::new(
+        tokens.next()?.parse().ok()?,
+        tokens.next()?.parse().ok()?,
+        tokens.next()?.parse().ok()?,
+    ))
+}
+
+/// The UDP socket and who is on the other end of it.
+#[derive(Resource)]
+pub struct NetSocket {
+    socket: UdpSocket,
+    /// The host's address for a guest; the guest's, once it has joined, for a host.
+    peer: Option<SocketAddr>,
+    connected: bool,
+}
+
+impl NetSocket {
+    /// Takes a guest asking to join as the peer, unless another guest has already joined.
+    fn accept(&mut self, sender: SocketAddr) -> bool {
+        if self.connected && self.peer != Some(sender) {
+            return false;
+        }
+        self.peer = Some(sender);
+        self.connected = true;
+        true
+    }
+
+    pub fn send(&self, message: &Message) {
+        let Some(peer) = self.peer else {
+            return;
+        };
+        if let Err(error) = self.socket.send_to(message.encode().as_bytes(), peer) {
+            eprintln!("Failed to send to {}: {}", peer, error);
+        }
+    }
+
+    /// Every message that has arrived since the last call, with its sender.
+    pub fn receive(&self) -> Vec<(Message, SocketAddr)> {
+        let mut messages = Vec::new();
+        let mut buffer = [0u8; 1500];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((length, sender)) => {
+                    let text = String::from_utf8_lossy(&buffer[..length]);
+                    match Message::decode(&text) {
+                        Some(message) => messages.push((message, sender)),
+                        None => eprintln!("Ignoring malformed message from {}: {}", sender, text),
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    eprintln!("Failed to receive: {}", error);
+                    break;
+                }
+            }
+        }
+        messages
+    }
+}
+
+/// Which host target a guest's mirrored target stands for, and the other way round.
+#[derive(Component, Clone, Copy)]
+struct NetTargetId(u32);
+
+/// A guest's bullet, flown by the host like its own.
+#[derive(Component)]
+struct GuestProjectile {
+    shot: u32,
+    velocity: Vec3,
+    gravity: f32,
+    damage: f32,
+    /// Flight the guest has already seen, still to be simulated.
+    catch_up_secs: f32,
+    age: Stopwatch,
+}
+
+/// Shows where the other player is.
+#[derive(Component)]
+struct Opponent;
+
+#[derive(Component)]
+struct DuelScoreText;
+
+/// The other player's eye position, as last reported.
+#[derive(Resource, Default)]
+struct OpponentPosition(Option<Vec3>);
+
+/// The guest's estimate of the host's clock.
+#[derive(Resource, Default)]
+struct HostClock {
+    /// Host time minus guest time, from the quickest message seen so far. Because every
+    /// estimate lags by the message's travel time, this maps a guest moment to the host moment
+    /// the guest was seeing.
+    offset: Option<f32>,
+}
+
+fn open_socket(mut commands: Commands, role: Res<NetRole>) {
+    let (bind, peer) = match &*role {
+        NetRole::Offline => return,
+        NetRole::Host { bind } => (bind.as_str(), None),
+        NetRole::Guest { server } => match server.parse::<SocketAddr>() {
+            Ok(server) if server.ip().is_loopback() => ("127.0.0.1:0", Some(server)),
+            Ok(server) => ("0.0.0.0:0", Some(server)),
+            Err(error) => {
+                eprintln!("Invalid host address {:?}: {}", server, error);
+                return;
+            }
+        },
+    };
+    let socket = match UdpSocket::bind(bind) {
+        Ok(socket) => socket,
+        Err(error) => {
+            eprintln!("Failed to open socket on {}: {}", bind, error);
+            return;
+        }
+    };
+    socket
+        .set_nonblocking(true)
+        .expect("UDP sockets support non-blocking mode");
+    println!("Listening on {:?}", socket.local_addr());
+    commands.insert_resource(NetSocket {
+        socket,
+        peer,
+        connected: false,
+    });
+
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(30.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        })
+        .with_child((Text::new("Waiting for opponent"), DuelScoreText));
+}
+
+fn host_receive(
+    socket: Option<ResMut<NetSocket>>,
+    mut resolver: ShotResolver,
+    rapier_context: ReadRapierContext,
+    player_query: Query<Entity, With<LogicalPlayer>>,
+    net_targets: Query<(&Transform, &Collider, &NetTargetId), With<Target>>,
+    targets: Query<(), With<Target>>,
+    history: Res<TargetHistory>,
+    loadout: Res<Loadout>,
+    scenario: Res<Scenario>,
+    target_rng: Res<TargetRng>,
+    mut score: ResMut<DuelScore>,
+    mut opponent: ResMut<OpponentPosition>,
+    mut since_guest_shot: Local<Option<Stopwatch>>,
+    time: Res<Time>,
+) {
+    let Some(mut socket) = socket else {
+        return;
+    };
+    if let Some(since_guest_shot) = since_guest_shot.as_mut() {
+        since_guest_shot.tick(time.delta());
+    }
+
+    for (message, sender) in socket.receive() {
+        if message == Message::Hello {
+            // A guest that missed the welcome asks again, but nobody else may take its place.
+            let joined = !socket.connected;
+            if !socket.accept(sender) {
+                println!("Ignoring join request from {} during a duel", sender);
+                continue;
+            }
+            if joined {
+                println!("Guest joined from {}", sender);
+            }
+            socket.send(&Message::Welcome {
+                seed: target_rng.seed,
+            });
+            for (transform, collider, id) in &net_targets {
+                socket.send(&spawn_message(id, transform, collider));
+            }
+            socket.send(&Message::Score {
+                host: score.host,
+                guest: score.guest,
+            });
+            continue;
+        }
+        if socket.peer != Some(sender) {
+            continue;
+        }
+
+        match message {
+            Message::Position { position, .. } => opponent.0 = Some(position),
+            Message::Shot {
+                shot,
+                time: shot_time,
+                slot,
+                origin,
+                direction,
+            } => {
+                // Reject shots from where the guest cannot be, or faster than any weapon fires.
+                let plausible_origin = opponent
+                    .0
+                    .is_some_and(|position| position.distance(origin) <= MAX_ORIGIN_ERROR);
+                let too_soon = since_guest_shot
+                    .as_ref()
+                    .is_some_and(|since| since.elapsed_secs() < MIN_SHOT_INTERVAL_SECS);
+                let Some(direction) = direction.try_normalize() else {
+                    continue;
+                };
+                if !plausible_origin || too_soon {
+                    println!("Rejected shot {} from guest", shot);
+                    socket.send(&Message::ShotResult { shot, hit: None });
+                    continue;
+                }
+                *since_guest_shot = Some(Stopwatch::new());
+                let now = time.elapsed_secs();
+                let shot_time = shot_time.clamp(now - history.0.window_secs(), now);
+                let damage = loadout.get(slot).damage;
+
+                if let Ballistics::Projectile { speed, gravity } = scenario.ballistics {
+                    // The bullet has been in flight on the guest's screen since it fired.
+                    resolver.commands.spawn((
+                        GuestProjectile {
+                            shot,
+                            velocity: direction * speed,
+                            gravity,
+                            damage,
+                            catch_up_secs: now - shot_time,
+                            age: Stopwatch::new(),
+                        },
+                        Transform::from_translation(origin),
+                    ));
+                    continue;
+                }
+
+                // Anything but a target that blocks the shot now blocked it then.
+                let not_target = |entity: Entity| !targets.contains(entity);
+                let filter = QueryFilter::new()
+                    .exclude_sensors()
+                    .exclude_rigid_body(player_query.single())
+                    .predicate(&not_target);
+                let blocked_at = rapier_context
+                    .single()
+                    .cast_ray(origin, direction, MAX_SHOT_DISTANCE, true, filter)
+                    .map_or(MAX_SHOT_DISTANCE, |(_, toi)| toi);
+
+                // Check targets where they were when the guest fired, as far back as the
+                // history goes.
+                let mut nearest: Option<(Entity, f32, Transform)> = None;
+                for (entity, past) in history.0.rewind(shot_time) {
+                    let Ok((_, collider, _)) = net_targets.get(entity) else {
+                        continue;
+                    };
+                    let mut collider = collider.clone();
+                    collider.set_scale(past.scale, 10);
+                    let Some(toi) = collider.cast_ray(
+                        past.translation,
+                        past.rotation,
+                        origin,
+                        direction,
+                        blocked_at,
+                        true,
+                    ) else {
+                        continue;
+                    };
+                    if nearest.is_none_or(|(_, nearest_toi, _)| toi < nearest_toi) {
+                        nearest = Some((entity, toi, past));
+                    }
+                }
+
+                let result = nearest.and_then(|(entity, toi, past)| {
+                    let (current, _, id) = net_targets.get(entity).expect("checked above");
+                    // The same spot on the target as it stands now.
+                    let position =
+                        origin + direction * toi - past.translation + current.translation;
+                    guest_hit(
+                        &mut resolver,
+                        &mut score,
+                        (entity, *id),
+                        position,
+                        direction,
+                        damage,
+                    )
+                });
+                socket.send(&Message::ShotResult { shot, hit: result });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Damages a target struck by one of the guest's shots, crediting the guest with any kill.
+fn guest_hit(
+    resolver: &mut ShotResolver,
+    score: &mut DuelScore,
+    (entity, id): (Entity, NetTargetId),
+    position: Vec3,
+    direction: Vec3,
+    damage: f32,
+) -> Option<ShotHit> {
+    let outcome = resolver.damage_target(entity, position, direction, damage)?;
+    if outcome.killed {
+        score.guest += 1;
+    }
+    Some(ShotHit {
+        target: id.0,
+        position,
+        damage: outcome.dealt,
+        killed: outcome.killed,
+        headshot: outcome.headshot,
+        target_age_secs: outcome.target_age_secs,
+    })
+}
+
+/// Flies the guest's bullets as `advance_projectiles` flies the host's own, and reports where
+/// each one lands. A new bullet first catches up with the flight the guest has already seen,
+/// against the targets as they stand now.
+fn host_advance_guest_projectiles(
+    socket: Option<Res<NetSocket>>,
+    mut resolver: ShotResolver,
+    mut projectiles: Query<(Entity, &mut GuestProjectile, &mut Transform), Without<Target>>,
+    ids: Query<&NetTargetId>,
+    player_query: Query<Entity, With<LogicalPlayer>>,
+    rapier_context: ReadRapierContext,
+    mut score: ResMut<DuelScore>,
+    time: Res<Time>,
+) {
+    let Some(socket) = socket else {
+        return;
+    };
+    let rapier_context = rapier_context.single();
+    let filter = QueryFilter::new()
+        .exclude_sensors()
+        .exclude_rigid_body(player_query.single());
+
+    for (entity, mut projectile, mut transform) in &mut projectiles {
+        let flight_secs = time.delta_secs() + std::mem::take(&mut projectile.catch_up_secs);
+        projectile
+            .age
+            .tick(std::time::Duration::from_secs_f32(flight_secs));
+        let mut remaining = flight_secs;
+        let mut struck = None;
+        while remaining > 0.0 {
+            let step = remaining.min(PROJECTILE_STEP_SECS);
+            remaining -= step;
+            let start = transform.translation;
+            let travel = projectile.velocity * step;
+            if let Some((hit_entity, toi)) =
+                rapier_context.cast_ray(start, travel, 1.0, true, filter)
+            {
+                struck = Some((hit_entity, start + travel * toi));
+                break;
+            }
+            transform.translation += travel;
+            projectile.velocity.y -= projectile.gravity * step;
+        }
+
+        let result = match struck {
+            Some((hit_entity, position)) => ids.get(hit_entity).ok().and_then(|id| {
+                guest_hit(
+                    &mut resolver,
+                    &mut score,
+                    (hit_entity, *id),
+                    position,
+                    projectile.velocity,
+                    projectile.damage,
+                )
+            }),
+            None if projectile.age.elapsed_secs() > PROJECTILE_LIFETIME_SECS => None,
+            None => continue,
+        };
+        resolver.commands.entity(entity).despawn();
+        socket.send(&Message::ShotResult {
+            shot: projectile.shot,
+            hit: result,
+        });
+    }
+}
+
+fn spawn_message(id: &NetTargetId, transform: &Transform, collider: &Collider) -> Message {
+    Message::Spawn {
+        id: id.0,
+        position: transform.translation,
+        radius: collider.as_ball().map_or(0.5, |ball| ball.radius()),
+    }
+}
+
+fn host_announce_targets(
+    mut commands: Commands,
+    socket: Option<Res<NetSocket>>,
+    new_targets: Query<(Entity, &Transform, &Collider), (Added<Target>, Without<NetTargetId>)>,
+    mut removed_targets: RemovedComponents<Target>,
+    mut entity_ids: Local<HashMap<Entity, u32>>,
+    mut next_id: Local<u32>,
+) {
+    let Some(socket) = socket else {
+        return;
+    };
+    for (entity, transform, collider) in &new_targets {
+        let id = NetTargetId(*next_id);
+        *next_id += 1;
+        commands.entity(entity).insert(id);
+        entity_ids.insert(entity, id.0);
+        socket.send(&spawn_message(&id, transform, collider));
+    }
+    for entity in removed_targets.read() {
+        if let Some(id) = entity_ids.remove(&entity) {
+            socket.send(&Message::Remove { id });
+        }
+    }
+}
+
+/// Reports targets that moved or shrank since they were last reported, so that the guest sees
+/// them where the host checks its shots against.
+fn host_send_target_moves(
+    socket: Option<Res<NetSocket>>,
+    net_targets: Query<(&Transform, &NetTargetId), With<Target>>,
+    mut sent: Local<HashMap<u32, Transform>>,
+    mut since_sent: Local<Stopwatch>,
+    time: Res<Time>,
+) {
+    let Some(socket) = socket else {
+        return;
+    };
+    since_sent.tick(time.delta());
+    if !socket.connected || since_sent.elapsed_secs() < POSITION_INTERVAL_SECS {
+        return;
+    }
+    since_sent.reset();
+    let mut reported = HashMap::default();
+    for (transform, id) in &net_targets {
+        // New targets were just announced where they stand.
+        let last = sent.get(&id.0).copied().unwrap_or(*transform);
+        if last.translation != transform.translation || last.scale != transform.scale {
+            socket.send(&Message::Move {
+                id: id.0,
+                position: transform.translation,
+                scale: transform.scale.x,
+            });
+        }
+        reported.insert(id.0, *transform);
+    }
+    *sent = reported;
+}
+
+fn host_sync_targets(
+    socket: Option<Res<NetSocket>>,
+    net_targets: Query<(&Transform, &Collider, &NetTargetId), With<Target>>,
+    mut since_sync: Local<Stopwatch>,
+    time: Res<Time>,
+) {
+    let Some(socket) = socket else {
+        return;
+    };
+    if !socket.connected {
+        return;
+    }
+    since_sync.tick(time.delta());
+    if since_sync.elapsed_secs() < SYNC_INTERVAL_SECS {
+        return;
+    }
+    since_sync.reset();
+    socket.send(&Message::Sync {
+        ids: net_targets.iter().map(|(_, _, id)| id.0).collect(),
+    });
+    for (transform, collider, id) in &net_targets {
+        socket.send(&spawn_message(id, transform, collider));
+    }
+}
+
+fn host_count_kills(
+    socket: Option<Res<NetSocket>>,
+    mut target_hits: EventReader<TargetHit>,
+    mut score: ResMut<DuelScore>,
+) {
+    for hit in target_hits.read() {
+        if hit.killed {
+            score.host += 1;
+        }
+    }
+    if let Some(socket) = socket {
+        if score.is_changed() {
+            socket.send(&Message::Score {
+                host: score.host,
+                guest: score.guest,
+            });
+        }
+    }
+}
+
+fn guest_connect(
+    socket: Option<Res<NetSocket>>,
+    mut since_hello: Local<Option<Stopwatch>>,
+    time: Res<Time>,
+) {
+    let Some(socket) = socket else {
+        return;
+    };
+    if socket.connected {
+        return;
+    }
+    let since_hello = since_hello.get_or_insert_with(|| {
+        let mut stopwatch = Stopwatch::new();
+        stopwatch.set_elapsed(std::time::Duration::from_secs_f32(HELLO_INTERVAL_SECS));
+        stopwatch
+    });
+    since_hello.tick(time.delta());
+    if since_hello.elapsed_secs() >= HELLO_INTERVAL_SECS {
+        since_hello.reset();
+        socket.send(&Message::Hello);
+    }
+}
+
+fn guest_receive(
+    mut commands: Commands,
+    socket: Option<ResMut<NetSocket>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut stats: ResMut<SessionStats>,
+    mut score: ResMut<DuelScore>,
+    mut target_hits: EventWriter<TargetHit>,
+    mut shots_missed: EventWriter<ShotMissed>,
+    mut opponent: ResMut<OpponentPosition>,
+    mut clock: ResMut<HostClock>,
+    mut target_rng: ResMut<TargetRng>,
+    mut transforms: Query<&mut Transform, With<NetTargetId>>,
+    mut id_entities: Local<HashMap<u32, Entity>>,
+    time: Res<Time>,
+) {
+    let Some(mut socket) = socket else {
+        return;
+    };
+    for (message, sender) in socket.receive() {
+        if socket.peer != Some(sender) {
+            continue;
+        }
+        match message {
+            Message::Welcome { seed } => {
+                if !socket.connected {
+                    println!("Joined host {} with target seed {}", sender, seed);
+                    // Saved sessions and submitted scores record the host's target sequence.
+                    *target_rng = TargetRng::new(seed);
+                }
+                socket.connected = true;
+            }
+            Message::Position {
+                position,
+                time: host_time,
+            } => {
+                opponent.0 = Some(position);
+                let offset = host_time - time.elapsed_secs();
+                if clock.offset.is_none_or(|best| offset > best) {
+                    clock.offset = Some(offset);
+                }
+            }
+            Message::Spawn {
+                id,
+                position,
+                radius,
+            } => {
+                // Repeated by every sync, with the target's current position.
+                if let Some(entity) = id_entities.get(&id) {
+                    if let Ok(mut transform) = transforms.get_mut(*entity) {
+                        transform.translation = position;
+                    }
+                    continue;
+                }
+                let target = spawn_target(
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                    position,
+                    radius,
+                    Color::srgb(0.9, 0.4, 0.1),
+                );
+                commands.entity(target).insert(NetTargetId(id));
+                id_entities.insert(id, target);
+            }
+            Message::Move {
+                id,
+                position,
+                scale,
+            } => {
+                let Some(entity) = id_entities.get(&id) else {
+                    continue;
+                };
+                if let Ok(mut transform) = transforms.get_mut(*entity) {
+                    transform.translation = position;
+                    transform.scale = Vec3::splat(scale);
+                }
+            }
+            Message::Remove { id } => {
+                if let Some(entity) = id_entities.remove(&id) {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            Message::Sync { ids } => {
+                // Targets whose removal was lost.
+                id_entities.retain(|id, entity| {
+                    let live = ids.contains(id);
+                    if !live {
+                        commands.entity(*entity).despawn_recursive();
+                    }
+                    live
+                });
+            }
+            Message::ShotResult { hit: None, .. } => {
+                stats.record_shot(false);
+                shots_missed.send(ShotMissed);
+            }
+            Message::ShotResult { hit: Some(hit), .. } => {
+                stats.record_shot(true);
+                stats.record_damage(hit.damage);
+                if hit.killed {
+                    stats.record_kill(hit.target_age_secs);
+                }
+                if let Some(entity) = id_entities.get(&hit.target) {
+                    target_hits.send(TargetHit {
+                        entity: *entity,
+                        position: hit.position,
+                        killed: hit.killed,
+                        headshot: hit.headshot,
+                    });
+                }
+            }
+            Message::Score { host, guest } => {
+                score.host = host;
+                score.guest = guest;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn guest_send_shots(
+    socket: Option<Res<NetSocket>>,
+    mut shots_fired: EventReader<ShotFired>,
+    clock: Res<HostClock>,
+    mut next_shot: Local<u32>,
+    time: Res<Time>,
+) {
+    let Some(socket) = socket else {
+        return;
+    };
+    for fired in shots_fired.read() {
+        socket.send(&Message::Shot {
+            shot: *next_shot,
+            time: time.elapsed_secs() + clock.offset.unwrap_or(0.0),
+            slot: fired.slot,
+            origin: fired.origin,
+            direction: fired.direction,
+        });
+        *next_shot += 1;
+    }
+}
+
+fn send_position(
+    socket: Option<Res<NetSocket>>,
+    camera: Query<&Transform, With<RenderPlayer>>,
+    mut since_sent: Local<Stopwatch>,
+    time: Res<Time>,
+) {
+    let Some(socket) = socket else {
+        return;
+    };
+    since_sent.tick(time.delta());
+    if since_sent.elapsed_secs() < POSITION_INTERVAL_SECS {
+        return;
+    }
+    since_sent.reset();
+    if let Ok(camera_transform) = camera.get_single() {
+        socket.send(&Message::Position {
+            position: camera_transform.translation,
+            time: time.elapsed_secs(),
+        });
+    }
+}
+
+/// Draws the other player as a capsule without a collider, so shots pass through it.
+fn show_opponent(
+    mut commands: Commands,
+    position: Res<OpponentPosition>,
+    mut opponent: Query<&mut Transform, With<Opponent>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let Some(eye) = position.0 else {
+        return;
+    };
+    // The capsule is centred below the eye.
+    let translation = eye - Vec3::Y * 0.8;
+    match opponent.get_single_mut() {
+        Ok(mut transform) => transform.translation = translation,
+        Err(_) => {
+            commands.spawn((
+                Opponent,
+                Transform::from_translation(translation),
+                Mesh3d(meshes.add(Capsule3d::new(0.4, 1.2))),
+                MeshMaterial3d(materials.add(StandardMaterial {
+                    base_color: Color::srgb(0.2, 0.4, 0.9),
+                    ..Default::default()
+                })),
+            ));
+        }
+    }
+}
+
+fn update_score_text(
+    socket: Option<Res<NetSocket>>,
+    role: Res<NetRole>,
+    score: Res<DuelScore>,
+    mut texts: Query<&mut Text, With<DuelScoreText>>,
+) {
+    let Some(socket) = socket else {
+        return;
+    };
+    if !socket.connected {
+        return;
+    }
+    let (mine, theirs) = if role.is_guest() {
+        (score.guest, score.host)
+    } else {
+        (score.host, score.guest)
+    };
+    for mut text in &mut texts {
+        text.0 = format!("You {} : {} Opponent", mine, theirs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    fn open(peer: Option<SocketAddr>) -> NetSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_nonblocking(true).unwrap();
+        NetSocket {
+            socket,
+            peer,
+            connected: false,
+        }
+    }
+
+    fn address(socket: &NetSocket) -> SocketAddr {
+        socket.socket.local_addr().unwrap()
+    }
+
+    /// Waits for at least one message to arrive.
+    fn receive(socket: &NetSocket) -> Vec<(Message, SocketAddr)> {
+        for _ in 0..200 {
+            let messages = socket.receive();
+            if !messages.is_empty() {
+                return messages;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        panic!("nothing arrived");
+    }
+
+    #[test]
+    fn messages_survive_encoding() {
+        let messages = [
+            Message::Hello,
+            Message::Welcome { seed: u64::MAX },
+            Message::Position {
+                position: Vec3::new(1.5, -2.25, 0.0),
+                time: 12.125,
+            },
+            Message::Spawn {
+                id: 7,
+                position: Vec3::new(0.1, 2.0, -30.0),
+                radius: 0.35,
+            },
+            Message::Move {
+                id: 7,
+                position: Vec3::new(-1.0, 2.5, 12.0),
+                scale: 0.5,
+            },
+            Message::Remove { id: 7 },
+            Message::Sync { ids: vec![] },
+            Message::Sync {
+                ids: vec![3, 4, 10],
+            },
+            Message::Shot {
+                shot: 42,
+                time: 3.5,
+                slot: WeaponSlot::Secondary,
+                origin: Vec3::new(0.0, 1.6, 0.0),
+                direction: Vec3::new(0.6, 0.0, -0.8),
+            },
+            Message::ShotResult {
+                shot: 42,
+                hit: None,
+            },
+            Message::ShotResult {
+                shot: 43,
+                hit: Some(ShotHit {
+                    target: 9,
+                    position: Vec3::new(1.0, 2.0, 3.0),
+                    damage: 25.0,
+                    killed: true,
+                    headshot: false,
+                    target_age_secs: 0.75,
+                }),
+            },
+            Message::Score { host: 3, guest: 5 },
+        ];
+        for message in messages {
+            assert_eq!(Message::decode(&message.encode()), Some(message));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        for text in [
+            "",
+            "BYE",
+            "WELCOME",
+            "SPAWN 1 2 3",
+            "SYNC 1 x",
+            "RESULT 1 MAYBE",
+        ] {
+            assert_eq!(Message::decode(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn host_and_guest_exchange_messages() {
+        let mut host = open(None);
+        let guest = open(Some(address(&host)));
+
+        guest.send(&Message::Hello);
+        let (message, sender) = receive(&host).remove(0);
+        assert_eq!(message, Message::Hello);
+        assert_eq!(sender, address(&guest));
+        assert!(host.accept(sender));
+
+        host.send(&Message::Welcome { seed: 99 });
+        host.send(&Message::Sync { ids: vec![1, 2] });
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            received.extend(receive(&guest).into_iter().map(|(message, _)| message));
+        }
+        assert_eq!(
+            received,
+            [
+                Message::Welcome { seed: 99 },
+                Message::Sync { ids: vec![1, 2] }
+            ]
+        );
+    }
+
+    #[test]
+    fn only_the_joined_guest_is_accepted() {
+        let mut host = open(None);
+        let guest = open(Some(address(&host)));
+        let intruder = open(Some(address(&host)));
+
+        assert!(host.accept(address(&guest)));
+        assert!(!host.accept(address(&intruder)));
+        assert_eq!(host.peer, Some(address(&guest)));
+        // The guest asking again, having missed the welcome, is still accepted.
+        assert!(host.accept(address(&guest)));
+    }
+}
@@ -0,0 +1,192 @@
+use crate::net::is_host;
+use crate::Target;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::collections::VecDeque;
+
+/// Records where targets were over the last `window_secs`, so that a shot can be checked
+/// against what its shooter saw rather than where targets are now.
+pub struct LagCompensationPlugin {
+    pub window_secs: f32,
+}
+
+impl Default for LagCompensationPlugin {
+    fn default() -> Self {
+        LagCompensationPlugin { window_secs: 1.0 }
+    }
+}
+
+impl Plugin for LagCompensationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TargetHistory(RewindBuffer::new(self.window_secs)));
+        // Only the host checks shots against the past.
+        app.add_systems(PostUpdate, record_target_history.run_if(is_host));
+    }
+}
+
+/// Recent transforms of every target, on the `Time` clock.
+#[derive(Resource)]
+pub struct TargetHistory(pub RewindBuffer);
+
+/// A transform at a moment in time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Snapshot {
+    pub time: f32,
+    pub transform: Transform,
+}
+
+/// Keeps a short history of transforms per entity and reconstructs them at past times.
+#[derive(Clone, Debug)]
+pub struct RewindBuffer {
+    window_secs: f32,
+    history: HashMap<Entity, VecDeque<Snapshot>>,
+}
+
+impl RewindBuffer {
+    pub fn new(window_secs: f32) -> Self {
+        RewindBuffer {
+            window_secs,
+            history: HashMap::default(),
+        }
+    }
+
+    pub fn window_secs(&self) -> f32 {
+        self.window_secs
+    }
+
+    /// Adds where `entity` was at `time`. Times must not go backwards for an entity.
+    pub fn record(&mut self, entity: Entity, time: f32, transform: Transform) {
+        let snapshots = self.history.entry(entity).or_default();
+        snapshots.push_back(Snapshot { time, transform });
+        // Keep one snapshot from before the window, so its start can still be interpolated.
+        let cutoff = time - self.window_secs;
+        while snapshots.len() > 1 && snapshots[1].time <= cutoff {
+            snapshots.pop_front();
+        }
+    }
+
+    /// Drops the history of an entity that no longer exists.
+    pub fn forget(&mut self, entity: Entity) {
+        self.history.remove(&entity);
+    }
+
+    /// Where `entity` was at `time`, interpolated between snapshots. Times after the latest
+    /// snapshot give the latest one. `None` if the entity is unknown or was not yet recorded.
+    pub fn sample(&self, entity: Entity, time: f32) -> Option<Transform> {
+        let snapshots = self.history.get(&entity)?;
+        let first = snapshots.front()?;
+        if time < first.time {
+            return None;
+        }
+        let after = snapshots.iter().position(|snapshot| snapshot.time >= time);
+        let Some(after) = after else {
+            return snapshots.back().map(|snapshot| snapshot.transform);
+        };
+        if after == 0 {
+            return Some(first.transform);
+        }
+        let (from, to) = (snapshots[after - 1], snapshots[after]);
+        let fraction = (time - from.time) / (to.time - from.time);
+        Some(Transform {
+            translation: from
+                .transform
+                .translation
+                .lerp(to.transform.translation, fraction),
+            rotation: from
+                .transform
+                .rotation
+                .slerp(to.transform.rotation, fraction),
+            scale: from.transform.scale.lerp(to.transform.scale, fraction),
+        })
+    }
+
+    /// Every entity that existed at `time`, with where it was.
+    pub fn rewind(&self, time: f32) -> impl Iterator<Item = (Entity, Transform)> + '_ {
+        self.history
+            .keys()
+            .filter_map(move |entity| Some((*entity, self.sample(*entity, time)?)))
+    }
+}
+
+fn record_target_history(
+    mut history: ResMut<TargetHistory>,
+    targets: Query<(Entity, &Transform), With<Target>>,
+    mut removed_targets: RemovedComponents<Target>,
+    time: Res<Time>,
+) {
+    for entity in removed_targets.read() {
+        history.0.forget(entity);
+    }
+    for (entity, transform) in &targets {
+        history.0.record(entity, time.elapsed_secs(), *transform);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: f32) -> Transform {
+        Transform::from_xyz(x, 0.0, 0.0)
+    }
+
+    #[test]
+    fn samples_between_recorded_times() {
+        let target = Entity::from_raw(1);
+        let mut buffer = RewindBuffer::new(1.0);
+        buffer.record(target, 0.0, at(0.0));
+        buffer.record(target, 0.1, at(1.0));
+        buffer.record(target, 0.2, at(3.0));
+
+        let sample = |time| buffer.sample(target, time).map(|t| t.translation.x);
+        assert_eq!(sample(-0.1), None);
+        assert_eq!(sample(0.0), Some(0.0));
+        assert!((sample(0.05).unwrap() - 0.5).abs() < 1e-4);
+        assert!((sample(0.15).unwrap() - 2.0).abs() < 1e-4);
+        // Later than the latest snapshot gives the latest one.
+        assert_eq!(sample(0.5), Some(3.0));
+        assert_eq!(buffer.sample(Entity::from_raw(2), 0.1), None);
+    }
+
+    #[test]
+    fn forgets_what_is_outside_the_window() {
+        let target = Entity::from_raw(1);
+        let mut buffer = RewindBuffer::new(1.0);
+        for step in 0..=10 {
+            let time = step as f32 * 0.25;
+            buffer.record(target, time, at(time));
+        }
+        // The latest time is 2.5, so the window starts at 1.5.
+        assert_eq!(buffer.sample(target, 1.0), None);
+        assert!(buffer.sample(target, 1.5).is_some());
+
+        buffer.forget(target);
+        assert_eq!(buffer.sample(target, 2.5), None);
+        assert_eq!(buffer.rewind(2.5).count(), 0);
+    }
+
+    #[test]
+    fn rewinds_by_a_synthetic_round_trip() {
+        // A target strafing at 4 m/s, recorded every frame at 60 Hz on the host's clock.
+        let target = Entity::from_raw(1);
+        let mut buffer = RewindBuffer::new(1.0);
+        let frame = 1.0 / 60.0;
+        let position = |time: f32| 4.0 * time;
+        for step in 0..=120 {
+            let time = step as f32 * frame;
+            buffer.record(target, time, at(position(time)));
+        }
+
+        // A guest 150 ms behind fired at what it saw then, and the shot arrives now.
+        let now = 120.0 * frame;
+        let round_trip = 0.15;
+        let seen_by_guest = position(now - round_trip);
+        let rewound: Vec<_> = buffer.rewind(now - round_trip).collect();
+        assert_eq!(rewound.len(), 1);
+        assert_eq!(rewound[0].0, target);
+        assert!((rewound[0].1.translation.x - seen_by_guest).abs() < 1e-3);
+        // Without rewinding, the shot would be checked 0.6 m off.
+        let current = buffer.sample(target, now).unwrap().translation.x;
+        assert!((current - seen_by_guest - 4.0 * round_trip).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,373 @@
+use crate::adaptive::AdaptiveDifficulty;
+use crate::history::unix_time_secs;
+use crate::net::spawns_targets;
+use crate::scenario::Scenario;
+use crate::stats::{RestartSession, SessionFinished, SessionStats};
+use crate::version::{CanonicalHasher, DrillVersion};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Finished routines are appended to this file, one JSON record per line.
+const ROUTINE_HISTORY_FILE: &str = "routines.jsonl";
+/// Pause between the end of one scenario and the start of the next, to read its summary.
+const BREAK_SECS: f32 = 5.0;
+
+/// Plays the scenarios of a routine one after the other, then shows a combined report and keeps
+/// it in the routine history. Restarting once the routine is over starts it again from the
+/// first scenario.
+pub struct RoutinePlugin {
+    pub routine: Option<ActiveRoutine>,
+}
+
+impl Plugin for RoutinePlugin {
+    fn build(&self, app: &mut App) {
+        let Some(routine) = &self.routine else {
+            return;
+        };
+        app.insert_resource(routine.clone());
+        app.insert_resource(RoutineHistory::load(PathBuf::from(ROUTINE_HISTORY_FILE)));
+        app.add_systems(
+            Update,
+            (restart_routine, finish_routine_step, advance_routine)
+                .chain()
+                .run_if(spawns_targets),
+        );
+    }
+}
+
+/// A list of scenarios played in order, shared as a `.routine.ron` file. Steps that name
+/// scenario files need those files too.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Routine {
+    pub name: String,
+    pub steps: Vec<RoutineStep>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoutineStep {
+    /// A built in scenario name, or a scenario file under `assets/`.
+    pub scenario: String,
+    /// How long to play the scenario for, instead of its own duration.
+    #[serde(default)]
+    pub duration_secs: Option<f32>,
+}
+
+/// How one scenario of a routine went.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StepResult {
+    pub scenario: String,
+    /// `DrillVersion::id` of the definitions the scenario was played with.
+    pub version: String,
+    pub score: f32,
+    pub accuracy: f32,
+    pub targets_killed: u32,
+    pub shots_fired: u32,
+    pub shots_hit: u32,
+    pub duration_secs: f32,
+}
+
+/// The routine being played and how far along it is.
+#[derive(Resource, Clone, Debug)]
+pub struct ActiveRoutine {
+    pub routine: Routine,
+    /// The scenario of each step, with its duration set by the routine.
+    scenarios: Vec<Scenario>,
+    pub step: usize,
+    pub results: Vec<StepResult>,
+    /// Running between two steps.
+    next_step: Option<Timer>,
+}
+
+impl ActiveRoutine {
+    /// Reads a routine file from under `assets/` and looks up all of its scenarios.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = Path::new("assets").join(path);
+        let text = std::fs::read_to_string(&file).map_err(|error| error.to_string())?;
+        let routine: Routine = ron::from_str(&text).map_err(|error| error.to_string())?;
+        if routine.steps.is_empty() {
+            return Err(String::from("the routine has no steps"));
+        }
+        let scenarios = routine
+            .steps
+            .iter()
+            .map(|step| {
+                let mut scenario = Scenario::find(&step.scenario)
+                    .map_err(|error| format!("{}: {}", step.scenario, error))?;
+                if let Some(duration_secs) = step.duration_secs {
+                    scenario.duration_secs = duration_secs;
+                }
+                Ok(scenario)
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(ActiveRoutine {
+            routine,
+            scenarios,
+            step: 0,
+            results: Vec::new(),
+            next_step: None,
+        })
+    }
+
+    /// Makes every scenario of the routine adaptive.
+    pub fn set_adaptive(&mut self, adaptive: AdaptiveDifficulty) {
+        for scenario in &mut self.scenarios {
+            scenario.adaptive = Some(adaptive);
+        }
+    }
+
+    /// The scenario of the current step.
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenarios[self.step]
+    }
+
+    pub fn finished(&self) -> bool {
+        self.results.len() == self.scenarios.len()
+    }
+
+    pub fn total_score(&self) -> f32 {
+        self.results.iter().map(|result| result.score).sum()
+    }
+
+    pub fn accuracy(&self) -> f32 {
+        let shots_fired: u32 = self.results.iter().map(|result| result.shots_fired).sum();
+        let shots_hit: u32 = self.results.iter().map(|result| result.shots_hit).sum();
+        if shots_fired == 0 {
+            return 1.0;
+        }
+        shots_hit as f32 / shots_fired as f32
+    }
+
+    /// Identifies the versions of every scenario played, so that totals are only compared
+    /// between runs of the same drills.
+    pub fn version(&self) -> String {
+        let mut hasher = CanonicalHasher::new("routine");
+        hasher.u64(self.results.len() as u64);
+        for result in &self.results {
+            hasher.str(&result.version);
+        }
+        hasher.finish().to_hex().to_string()
+    }
+}
+
+/// The result of one finished routine.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoutineRecord {
+    pub routine: String,
+    /// `ActiveRoutine::version` of the run.
+    pub version: String,
+    pub total_score: f32,
+    pub accuracy: f32,
+    pub steps: Vec<StepResult>,
+    /// Seconds since the Unix epoch.
+    pub finished_at: u64,
+}
+
+/// Every routine finished on this machine.
+#[derive(Resource, Debug)]
+pub struct RoutineHistory {
+    path: PathBuf,
+    pub records: Vec<RoutineRecord>,
+}
+
+impl RoutineHistory {
+    /// Reads the history file, skipping lines that cannot be parsed.
+    pub fn load(path: PathBuf) -> Self {
+        let mut records = Vec::new();
+        match File::open(&path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines().map_while(Result::ok) {
+                    match serde_json::from_str(&line) {
+                        Ok(record) => records.push(record),
+                        Err(error) => eprintln!("Skipping routine record: {}", error),
+                    }
+                }
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => eprintln!("Could not read {}: {}", path.display(), error),
+        }
+        RoutineHistory { path, records }
+    }
+
+    pub fn append(&mut self, record: RoutineRecord) -> io::Result<()> {
+        self.records.push(record.clone());
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        file.write_all(&line)
+    }
+
+    /// Earlier runs of a routine on the same version, in the order they were played.
+    pub fn runs<'a>(
+        &'a self,
+        routine: &'a str,
+        version: &'a str,
+    ) -> impl Iterator<Item = &'a RoutineRecord> {
+        self.records
+            .iter()
+            .filter(move |record| record.routine == routine && record.version == version)
+    }
+}
+
+#[derive(Component)]
+struct RoutineReport;
+
+/// Starts the routine again from its first scenario when restarting after it is over. Restarting
+/// during the break replays the scenario just finished instead of moving on.
+fn restart_routine(
+    mut commands: Commands,
+    mut restarts: EventReader<RestartSession>,
+    mut routine: ResMut<ActiveRoutine>,
+    mut scenario: ResMut<Scenario>,
+    reports: Query<Entity, With<RoutineReport>>,
+) {
+    if restarts.read().last().is_none() {
+        return;
+    }
+    if routine.next_step.take().is_some() {
+        routine.results.pop();
+    }
+    if !routine.finished() {
+        return;
+    }
+    for entity in &reports {
+        commands.entity(entity).despawn_recursive();
+    }
+    routine.step = 0;
+    routine.results.clear();
+    *scenario = routine.scenario().clone();
+    // The restart just read was for the last scenario, so restart again with the first.
+    commands.send_event(RestartSession);
+}
+
+fn finish_routine_step(
+    mut commands: Commands,
+    mut session_finished: EventReader<SessionFinished>,
+    mut routine: ResMut<ActiveRoutine>,
+    mut history: ResMut<RoutineHistory>,
+    scenario: Res<Scenario>,
+    stats: Res<SessionStats>,
+    version: Res<DrillVersion>,
+) {
+    if session_finished.read().last().is_none() || routine.finished() {
+        return;
+    }
+    routine.results.push(StepResult {
+        scenario: scenario.name.clone(),
+        version: version.id(),
+        score: scenario.score(&stats),
+        accuracy: stats.accuracy(),
+        targets_killed: stats.targets_killed,
+        shots_fired: stats.shots_fired,
+        shots_hit: stats.shots_hit,
+        duration_secs: stats.elapsed.elapsed_secs(),
+    });
+    if !routine.finished() {
+        routine.next_step = Some(Timer::from_seconds(BREAK_SECS, TimerMode::Once));
+        return;
+    }
+
+    let record = RoutineRecord {
+        routine: routine.routine.name.clone(),
+        version: routine.version(),
+        total_score: routine.total_score(),
+        accuracy: routine.accuracy(),
+        steps: routine.results.clone(),
+        finished_at: unix_time_secs(),
+    };
+    let earlier_runs = history.runs(&record.routine, &record.version).count();
+    let best = history
+        .runs(&record.routine, &record.version)
+        .map(|run| run.total_score)
+        .reduce(f32::max);
+    if let Err(error) = history.append(record.clone()) {
+        eprintln!("Could not save routine: {}", error);
+    }
+    spawn_report(&mut commands, &record, earlier_runs, best);
+}
+
+/// Moves on to the next scenario once the break is over.
+fn advance_routine(
+    mut routine: ResMut<ActiveRoutine>,
+    mut scenario: ResMut<Scenario>,
+    mut restarts: EventWriter<RestartSession>,
+    time: Res<Time>,
+) {
+    let Some(timer) = &mut routine.next_step else {
+        return;
+    };
+    if !timer.tick(time.delta()).finished() {
+        return;
+    }
+    routine.next_step = None;
+    routine.step += 1;
+    *scenario = routine.scenario().clone();
+    restarts.send(RestartSession);
+}
+
+fn spawn_report(
+    commands: &mut Commands,
+    record: &RoutineRecord,
+    earlier_runs: usize,
+    best: Option<f32>,
+) {
+    let mut lines = vec![format!("Routine {} complete", record.routine)];
+    for step in &record.steps {
+        lines.push(format!(
+            "{}: {:.0}  ({:.0}%)",
+            step.scenario,
+            step.score,
+            step.accuracy * 100.0
+        ));
+    }
+    lines.push(format!(
+        "Total: {:.0}  |  Accuracy: {:.0}%",
+        record.total_score,
+        record.accuracy * 100.0
+    ));
+    match best {
+        Some(best) if record.total_score > best => lines.push(format!(
+            "New best, up from {:.0} over {} runs",
+            best, earlier_runs
+        )),
+        Some(best) => lines.push(format!("Best: {:.0} over {} runs", best, earlier_runs)),
+        None => lines.push(String::from("First run of this routine")),
+    }
+
+    // Kept to the right, clear of the last scenario's summary.
+    commands
+        .spawn((
+            RoutineReport,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::FlexEnd,
+                align_items: AlignItems::Center,
+                padding: UiRect::right(Val::Px(40.0)),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all(Val::Px(20.0)),
+                        row_gap: Val::Px(6.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+                ))
+                .with_children(|panel| {
+                    for line in lines {
+                        panel.spawn(Text::new(line));
+                    }
+                });
+        });
+}
@@ -0,0 +1,990 @@
+use crate::adaptive::AdaptiveDifficulty;
+use crate::arena::{ArenaCollider, ArenaSource, SpawnVolume};
+use crate::skills::SkillWeights;
+use crate::stats::SessionStats;
+use bevy::prelude::*;
+use rand::distr::Uniform;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{PI, TAU};
+use std::path::Path;
+
+/// Lowest height a target centre may be placed at, just above the ground.
+const MIN_TARGET_HEIGHT: f32 = 0.0;
+
+/// How often placement is retried before accepting a target below the ground.
+const PLACEMENT_ATTEMPTS: usize = 16;
+
+/// Describes a training drill: how targets are sized and where they appear.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    /// Length of a session in seconds.
+    pub duration_secs: f32,
+    /// How many targets are alive at once. Zero leaves spawning entirely to the script.
+    pub target_count: usize,
+    pub target_size: TargetSize,
+    pub target_placement: TargetPlacement,
+    pub target_motion: TargetMotion,
+    /// Targets left alive this long are removed and count as missed.
+    pub target_timeout: Option<TargetTimeout>,
+    /// Damage a target absorbs before it is killed. Each hit deals one point of damage.
+    pub target_health: f32,
+    pub score_formula: ScoreFormula,
+    pub target_audio: TargetAudio,
+    pub arena: ArenaSource,
+    /// Times how quickly shots follow the end of a strafe, and shows the result after each shot.
+    pub counter_strafe_drill: bool,
+    pub ballistics: Ballistics,
+    /// Rhai script under `assets/` adding custom logic through hooks. See `scripting`.
+    pub script: Option<String>,
+    /// Tunes target size, lifetime and speed to the player during the session.
+    pub adaptive: Option<AdaptiveDifficulty>,
+    /// Which skills the drill trains, for the skill ratings.
+    #[serde(default)]
+    pub skills: SkillWeights,
+}
+
+/// How shots travel to what they hit.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum Ballistics {
+    /// Shots land instantly along a straight line.
+    #[default]
+    Hitscan,
+    /// Bullets fly at `speed` metres per second and drop under `gravity`, so distant or moving
+    /// targets have to be led.
+    Projectile { speed: f32, gravity: f32 },
+}
+
+/// Spatial sounds emitted by targets, so they can be found by ear.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TargetAudio {
+    /// A short cue from the target's position when it appears.
+    pub spawn_cue: bool,
+    /// A looping hum from every live target.
+    pub hum: bool,
+}
+
+/// How a session's stats are turned into a single score.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub enum ScoreFormula {
+    /// One point per hit, minus one per missed shot and the timeout penalty per expired target.
+    #[default]
+    Classic,
+    /// 100 points per kill, scaled by accuracy.
+    AccuracyWeighted,
+    /// 100 points per kill, plus up to `max_bonus` for each kill made faster than `par_secs`.
+    TimeBonus { par_secs: f32, max_bonus: f32 },
+    /// One point per kill. Misses and expired targets cost nothing.
+    PenaltyFree,
+}
+
+/// How a target moves once spawned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum TargetMotion {
+    #[default]
+    Static,
+    /// Swings side to side across the player's line of sight at spawn, peaking at `speed`
+    /// metres per second and covering `distance` metres end to end.
+    Strafe { speed: f32, distance: f32 },
+}
+
+/// How long a target lives before it expires unshot.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TargetTimeout {
+    pub lifetime_secs: f32,
+    /// Points lost when a target expires, separate from the penalty for a missed shot.
+    pub penalty: i32,
+    /// Shrink the target towards nothing as it approaches its timeout.
+    pub shrink: bool,
+}
+
+/// How big a freshly spawned target is.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum TargetSize {
+    /// Radius in world units, so perceived size depends on where the player stands.
+    Radius { min: f32, max: f32 },
+    /// Angle in degrees the target subtends from the player's eye at spawn.
+    Angular { min_deg: f32, max_deg: f32 },
+}
+
+/// Where a freshly spawned target is placed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum TargetPlacement {
+    /// Uniformly inside a fixed box in world space.
+    Box { min: Vec3, max: Vec3 },
+    /// At an angle in degrees away from the current crosshair, at a distance from the eye.
+    CrosshairOffset {
+        min_deg: f32,
+        max_deg: f32,
+        min_distance: f32,
+        max_distance: f32,
+    },
+    /// Spread evenly over the cone of `half_angle_deg` around the crosshair.
+    ViewCone {
+        half_angle_deg: f32,
+        min_distance: f32,
+        max_distance: f32,
+    },
+    /// Within `spread_deg` of the direction straight behind the player, for 180° turns.
+    Behind {
+        spread_deg: f32,
+        min_distance: f32,
+        max_distance: f32,
+    },
+    /// Exactly `offset_deg` away from the last killed target, in a random direction.
+    /// Falls back to the crosshair before the first kill.
+    FromLastKill {
+        offset_deg: f32,
+        min_distance: f32,
+        max_distance: f32,
+    },
+    /// On a sphere of `radius` around the eye, between two elevations in degrees.
+    Sphere {
+        radius: f32,
+        min_elevation_deg: f32,
+        max_elevation_deg: f32,
+    },
+    /// Inside the target spawn volumes marked in the arena file, larger volumes more often.
+    /// Falls back to straight ahead of the crosshair if the arena has none.
+    ArenaVolumes,
+    /// In a free cell of a `columns` × `rows` grid on the vertical plane through `center`,
+    /// never the cell the previous target was killed in.
+    Grid {
+        center: Vec3,
+        columns: usize,
+        rows: usize,
+        spacing: f32,
+    },
+}
+
+/// What a spawn strategy may take into account when placing a target.
+pub struct SpawnContext<'a> {
+    /// The player's eye.
+    pub view: &'a Transform,
+    pub last_kill: Option<Vec3>,
+    /// Grid cell of the target being replaced, which the new one must avoid.
+    pub vacated_cell: Option<usize>,
+    pub spawn_volumes: &'a [SpawnVolume],
+}
+
+/// A sampled target ready to be spawned.
+#[derive(Clone, Copy, Debug)]
+pub struct TargetSpawn {
+    pub position: Vec3,
+    pub radius: f32,
+    /// The grid cell claimed for the target, if the placement uses a grid.
+    pub cell: Option<usize>,
+}
+
+/// Tracks which cells of a grid placement hold a target.
+#[derive(Resource, Default, Debug)]
+pub struct GridOccupancy {
+    occupied: Vec<bool>,
+}
+
+impl GridOccupancy {
+    pub fn new(cells: usize) -> Self {
+        GridOccupancy {
+            occupied: vec![false; cells],
+        }
+    }
+
+    /// Claims a random free cell other than `exclude`.
+    pub fn claim(&mut self, exclude: Option<usize>, rng: &mut impl Rng) -> Option<usize> {
+        let free = (0..self.occupied.len())
+            .filter(|&cell| !self.occupied[cell] && Some(cell) != exclude)
+            .collect::<Vec<_>>();
+        let cell = *free.choose(rng)?;
+        self.occupied[cell] = true;
+        Some(cell)
+    }
+
+    pub fn release(&mut self, cell: usize) {
+        if let Some(occupied) = self.occupied.get_mut(cell) {
+            *occupied = false;
+        }
+    }
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            name: String::from("default"),
+            duration_secs: 60.0,
+            target_count: 3,
+            target_size: TargetSize::Radius { min: 0.3, max: 0.8 },
+            target_placement: TargetPlacement::Box {
+                min: Vec3::new(-4.0, 2.0, 1.0),
+                max: Vec3::new(4.0, 5.0, 2.0),
+            },
+            target_motion: TargetMotion::Static,
+            target_timeout: None,
+            target_health: 1.0,
+            score_formula: ScoreFormula::Classic,
+            target_audio: TargetAudio::default(),
+            arena: ArenaSource::Builtin,
+            counter_strafe_drill: false,
+            ballistics: Ballistics::Hitscan,
+            script: None,
+            adaptive: None,
+            skills: SkillWeights {
+                flicking: 0.5,
+                switching: 0.5,
+                ..default()
+            },
+        }
+    }
+}
+
+impl Scenario {
+    /// Wide flicks from the crosshair to a small target.
+    pub fn flick() -> Self {
+        Scenario {
+            name: String::from("flick"),
+            duration_secs: 60.0,
+            target_count: 3,
+            target_size: TargetSize::Angular {
+                min_deg: 1.0,
+                max_deg: 1.0,
+            },
+            target_placement: TargetPlacement::CrosshairOffset {
+                min_deg: 40.0,
+                max_deg: 90.0,
+                min_distance: 8.0,
+                max_distance: 12.0,
+            },
+            target_motion: TargetMotion::Static,
+            target_timeout: None,
+            target_health: 1.0,
+            score_formula: ScoreFormula::Classic,
+            target_audio: TargetAudio::default(),
+            arena: ArenaSource::Builtin,
+            counter_strafe_drill: false,
+            ballistics: Ballistics::Hitscan,
+            script: None,
+            adaptive: None,
+            skills: SkillWeights {
+                flicking: 1.0,
+                ..default()
+            },
+        }
+    }
+
+    /// Targets anywhere in front of the crosshair.
+    pub fn view_cone() -> Self {
+        Scenario {
+            name: String::from("view_cone"),
+            duration_secs: 60.0,
+            target_count: 3,
+            target_size: TargetSize::Angular {
+                min_deg: 2.0,
+                max_deg: 4.0,
+            },
+            target_placement: TargetPlacement::ViewCone {
+                half_angle_deg: 35.0,
+                min_distance: 8.0,
+                max_distance: 12.0,
+            },
+            target_motion: TargetMotion::Static,
+            target_timeout: None,
+            target_health: 1.0,
+            score_formula: ScoreFormula::Classic,
+            target_audio: TargetAudio::default(),
+            arena: ArenaSource::Builtin,
+            counter_strafe_drill: false,
+            ballistics: Ballistics::Hitscan,
+            script: None,
+            adaptive: None,
+            skills: SkillWeights {
+                flicking: 0.6,
+                switching: 0.4,
+                ..default()
+            },
+        }
+    }
+
+    /// Every target appears behind the player.
+    pub fn turn_180() -> Self {
+        Scenario {
+            name: String::from("turn_180"),
+            duration_secs: 60.0,
+            target_count: 3,
+            target_size: TargetSize::Angular {
+                min_deg: 3.0,
+                max_deg: 3.0,
+            },
+            target_placement: TargetPlacement::Behind {
+                spread_deg: 20.0,
+                min_distance: 8.0,
+                max_distance: 12.0,
+            },
+            target_motion: TargetMotion::Static,
+            target_timeout: None,
+            target_health: 1.0,
+            score_formula: ScoreFormula::Classic,
+            target_audio: TargetAudio::default(),
+            arena: ArenaSource::Builtin,
+            counter_strafe_drill: false,
+            ballistics: Ballistics::Hitscan,
+            script: None,
+            adaptive: None,
+            skills: SkillWeights {
+                flicking: 0.7,
+                reaction: 0.3,
+                ..default()
+            },
+        }
+    }
+
+    /// Each target appears a fixed flick away from the previous kill.
+    pub fn kill_chain() -> Self {
+        Scenario {
+            name: String::from("kill_chain"),
+            duration_secs: 60.0,
+            target_count: 3,
+            target_size: TargetSize::Angular {
+                min_deg: 2.5,
+                max_deg: 2.5,
+            },
+            target_placement: TargetPlacement::FromLastKill {
+                offset_deg: 15.0,
+                min_distance: 10.0,
+                max_distance: 10.0,
+            },
+            target_motion: TargetMotion::Static,
+            target_timeout: None,
+            target_health: 1.0,
+            score_formula: ScoreFormula::Classic,
+            target_audio: TargetAudio::default(),
+            arena: ArenaSource::Builtin,
+            counter_strafe_drill: false,
+            ballistics: Ballistics::Hitscan,
+            script: None,
+            adaptive: None,
+            skills: SkillWeights {
+                switching: 1.0,
+                ..default()
+            },
+        }
+    }
+
+    /// Targets all around the player.
+    pub fn sphere() -> Self {
+        Scenario {
+            name: String::from("sphere"),
+            duration_secs: 60.0,
+            target_count: 3,
+            target_size: TargetSize::Angular {
+                min_deg: 3.0,
+                max_deg: 3.0,
+            },
+            target_placement: TargetPlacement::Sphere {
+                radius: 10.0,
+                min_elevation_deg: 0.0,
+                max_elevation_deg: 45.0,
+            },
+            target_motion: TargetMotion::Static,
+            target_timeout: None,
+            target_health: 1.0,
+            score_formula: ScoreFormula::Classic,
+            target_audio: TargetAudio::default(),
+            arena: ArenaSource::Builtin,
+            counter_strafe_drill: false,
+            ballistics: Ballistics::Hitscan,
+            script: None,
+            adaptive: None,
+            skills: SkillWeights {
+                flicking: 0.5,
+                switching: 0.5,
+                ..default()
+            },
+        }
+    }
+
+    /// Targets in the spawn volumes of the courtyard arena.
+    pub fn courtyard() -> Self {
+        Scenario {
+            name: String::from("courtyard"),
+            duration_secs: 60.0,
+            target_count: 3,
+            target_size: TargetSize::Angular {
+                min_deg: 2.0,
+                max_deg: 3.0,
+            },
+            target_placement: TargetPlacement::ArenaVolumes,
+            target_motion: TargetMotion::Static,
+            target_timeout: None,
+            target_health: 1.0,
+            score_formula: ScoreFormula::Classic,
+            target_audio: TargetAudio::default(),
+            arena: ArenaSource::Gltf {
+                path: String::from("arenas/courtyard.glb"),
+                collider: ArenaCollider::TriMesh,
+            },
+            counter_strafe_drill: false,
+            ballistics: Ballistics::Hitscan,
+            script: None,
+            adaptive: None,
+            skills: SkillWeights {
+                flicking: 0.5,
+                switching: 0.5,
+                ..default()
+            },
+        }
+    }
+
+    /// Several targets on a wall grid, replaced in a new cell as soon as one is killed.
+    pub fn gridshot() -> Self {
+        Scenario {
+            name: String::from("gridshot"),
+            duration_secs: 60.0,
+            target_count: 3,
+            target_size: TargetSize::Radius {
+                min: 0.35,
+                max: 0.35,
+            },
+            target_placement: TargetPlacement::Grid {
+                center: Vec3::new(0.0, 1.5, 9.0),
+                columns: 5,
+                rows: 3,
+                spacing: 1.0,
+            },
+            target_motion: TargetMotion::Static,
+            target_timeout: None,
+            target_health: 1.0,
+            score_formula: ScoreFormula::AccuracyWeighted,
+            target_audio: TargetAudio::default(),
+            arena: ArenaSource::Builtin,
+            counter_strafe_drill: false,
+            ballistics: Ballistics::Hitscan,
+            script: None,
+            adaptive: None,
+            skills: SkillWeights {
+                flicking: 0.3,
+                switching: 0.7,
+                ..default()
+            },
+        }
+    }
+
+    /// Targets that shrink away quickly unless shot.
+    pub fn reaction() -> Self {
+        Scenario {
+            name: String::from("reaction"),
+            duration_secs: 60.0,
+            target_count: 1,
+            target_size: TargetSize::Angular {
+                min_deg: 3.0,
+                max_deg: 3.0,
+            },
+            target_placement: TargetPlacement::ViewCone {
+                half_angle_deg: 30.0,
+                min_distance: 8.0,
+                max_distance: 12.0,
+            },
+            target_motion: TargetMotion::Static,
+            target_timeout: Some(TargetTimeout {
+                lifetime_secs: 1.0,
+                penalty: 2,
+                shrink: true,
+            }),
+            target_health: 1.0,
+            score_formula: ScoreFormula::TimeBonus {
+                par_secs: 0.6,
+                max_bonus: 100.0,
+            },
+            target_audio: TargetAudio::default(),
+            arena: ArenaSource::Builtin,
+            counter_strafe_drill: false,
+            ballistics: Ballistics::Hitscan,
+            script: None,
+            adaptive: None,
+            skills: SkillWeights {
+                reaction: 1.0,
+                ..default()
+            },
+        }
+    }
+
+    /// Targets appear behind the player and have to be found by ear.
+    pub fn audio_turn() -> Self {
+        Scenario {
+            name: String::from("audio_turn"),
+            duration_secs: 60.0,
+            target_count: 1,
+            target_size: TargetSize::Angular {
+                min_deg: 4.0,
+                max_deg: 4.0,
+            },
+            target_placement: TargetPlacement::Behind {
+                spread_deg: 60.0,
+                min_distance: 6.0,
+                max_distance: 10.0,
+            },
+            target_motion: TargetMotion::Static,
+            target_timeout: None,
+            target_health: 1.0,
+            score_formula: ScoreFormula::TimeBonus {
+                par_secs: 1.5,
+                max_bonus: 100.0,
+            },
+            target_audio: TargetAudio {
+                spawn_cue: true,
+                hum: true,
+            },
+            arena: ArenaSource::Builtin,
+            counter_strafe_drill: false,
+            ballistics: Ballistics::Hitscan,
+            script: None,
+            adaptive: None,
+            skills: SkillWeights {
+                flicking: 0.4,
+                reaction: 0.6,
+                ..default()
+            },
+        }
+    }
+
+    /// Small, distant targets straight ahead, to be shot between strafes.
+    pub fn counter_strafe() -> Self {
+        Scenario {
+            name: String::from("counter_strafe"),
+            duration_secs: 60.0,
+            target_count: 1,
+            target_size: TargetSize::Angular {
+                min_deg: 1.5,
+                max_deg: 2.0,
+            },
+            target_placement: TargetPlacement::ViewCone {
+                half_angle_deg: 10.0,
+                min_distance: 12.0,
+                max_distance: 18.0,
+            },
+            target_motion: TargetMotion::Static,
+            target_timeout: None,
+            target_health: 1.0,
+            score_formula: ScoreFormula::AccuracyWeighted,
+            target_audio: TargetAudio::default(),
+            arena: ArenaSource::Builtin,
+            counter_strafe_drill: true,
+            ballistics: Ballistics::Hitscan,
+            script: None,
+            adaptive: None,
+            skills: SkillWeights {
+                flicking: 0.6,
+                spray_control: 0.4,
+                ..default()
+            },
+        }
+    }
+
+    /// Distant targets hit by slow, dropping bullets, for practising leading and holdover.
+    pub fn long_range() -> Self {
+        Scenario {
+            name: String::from("long_range"),
+            duration_secs: 60.0,
+            target_count: 2,
+            target_size: TargetSize::Angular {
+                min_deg: 1.5,
+                max_deg: 2.5,
+            },
+            target_placement: TargetPlacement::ViewCone {
+                half_angle_deg: 25.0,
+                min_distance: 30.0,
+                max_distance: 60.0,
+            },
+            target_motion: TargetMotion::Static,
+            target_timeout: None,
+            target_health: 1.0,
+            score_formula: ScoreFormula::AccuracyWeighted,
+            target_audio: TargetAudio::default(),
+            arena: ArenaSource::Builtin,
+            counter_strafe_drill: false,
+            ballistics: Ballistics::Projectile {
+                speed: 80.0,
+                gravity: 9.81,
+            },
+            script: None,
+            adaptive: None,
+            skills: SkillWeights {
+                flicking: 0.6,
+                tracking: 0.4,
+                ..default()
+            },
+        }
+    }
+
+    /// A single target that a script moves 30° left of each kill, shrinking it every time.
+    pub fn shrinking_chain() -> Self {
+        Scenario {
+            name: String::from("shrinking_chain"),
+            duration_secs: 60.0,
+            target_count: 0,
+            target_size: TargetSize::Radius { min: 0.5, max: 0.5 },
+            target_placement: TargetPlacement::CrosshairOffset {
+                min_deg: 0.0,
+                max_deg: 0.0,
+                min_distance: 10.0,
+                max_distance: 10.0,
+            },
+            target_motion: TargetMotion::Static,
+            target_timeout: None,
+            target_health: 1.0,
+            score_formula: ScoreFormula::Classic,
+            target_audio: TargetAudio::default(),
+            arena: ArenaSource::Builtin,
+            counter_strafe_drill: false,
+            ballistics: Ballistics::Hitscan,
+            script: Some(String::from("scripts/shrinking_chain.rhai")),
+            adaptive: None,
+            skills: SkillWeights {
+                flicking: 0.4,
+                switching: 0.6,
+                ..default()
+            },
+        }
+    }
+
+    /// A sturdy target strafing across the view, to be followed with the crosshair.
+    pub fn tracking() -> Self {
+        Scenario {
+            name: String::from("tracking"),
+            duration_secs: 60.0,
+            target_count: 1,
+            target_size: TargetSize::Radius { min: 0.5, max: 0.5 },
+            target_placement: TargetPlacement::ViewCone {
+                half_angle_deg: 20.0,
+                min_distance: 10.0,
+                max_distance: 15.0,
+            },
+            target_motion: TargetMotion::Strafe {
+                speed: 4.0,
+                distance: 6.0,
+            },
+            target_timeout: None,
+            target_health: 20.0,
+            score_formula: ScoreFormula::Classic,
+            target_audio: TargetAudio::default(),
+            arena: ArenaSource::Builtin,
+            counter_strafe_drill: false,
+            ballistics: Ballistics::Hitscan,
+            script: None,
+            adaptive: None,
+            skills: SkillWeights {
+                tracking: 1.0,
+                ..default()
+            },
+        }
+    }
+
+    /// A distant target that takes a long burst to kill, so the spray has to be held on it.
+    pub fn spray_control() -> Self {
+        Scenario {
+            name: String::from("spray_control"),
+            duration_secs: 60.0,
+            target_count: 1,
+            target_size: TargetSize::Angular {
+                min_deg: 4.0,
+                max_deg: 4.0,
+            },
+            target_placement: TargetPlacement::ViewCone {
+                half_angle_deg: 10.0,
+                min_distance: 15.0,
+                max_distance: 20.0,
+            },
+            target_motion: TargetMotion::Static,
+            target_timeout: None,
+            target_health: 10.0,
+            score_formula: ScoreFormula::Classic,
+            target_audio: TargetAudio::default(),
+            arena: ArenaSource::Builtin,
+            counter_strafe_drill: false,
+            ballistics: Ballistics::Hitscan,
+            script: None,
+            adaptive: None,
+            skills: SkillWeights {
+                spray_control: 1.0,
+                ..default()
+            },
+        }
+    }
+
+    /// Looks up one of the built-in scenarios.
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Scenario::default()),
+            "flick" => Some(Scenario::flick()),
+            "view_cone" => Some(Scenario::view_cone()),
+            "turn_180" => Some(Scenario::turn_180()),
+            "kill_chain" => Some(Scenario::kill_chain()),
+            "sphere" => Some(Scenario::sphere()),
+            "gridshot" => Some(Scenario::gridshot()),
+            "reaction" => Some(Scenario::reaction()),
+            "audio_turn" => Some(Scenario::audio_turn()),
+            "courtyard" => Some(Scenario::courtyard()),
+            "counter_strafe" => Some(Scenario::counter_strafe()),
+            "long_range" => Some(Scenario::long_range()),
+            "shrinking_chain" => Some(Scenario::shrinking_chain()),
+            "tracking" => Some(Scenario::tracking()),
+            "spray_control" => Some(Scenario::spray_control()),
+            _ => None,
+        }
+    }
+
+    /// Looks up a built in scenario by name, or reads a `.ron` scenario file from under `assets/`.
+    pub fn find(name_or_path: &str) -> Result<Self, String> {
+        if name_or_path.ends_with(".ron") {
+            Scenario::load(&Path::new("assets").join(name_or_path))
+        } else {
+            Scenario::by_name(name_or_path).ok_or_else(|| String::from("unknown scenario"))
+        }
+    }
+
+    /// Reads a scenario file written by `save`.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        ron::from_str(&text).map_err(|error| error.to_string())
+    }
+
+    /// Writes the scenario as RON, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+        }
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())?;
+        std::fs::write(path, text).map_err(|error| error.to_string())
+    }
+
+    /// Rates a session using this scenario's score formula, plus any points awarded by its script.
+    pub fn score(&self, stats: &SessionStats) -> f32 {
+        let kills = stats.targets_killed as f32;
+        let score = match self.score_formula {
+            ScoreFormula::Classic => {
+                let expiry_penalty = self
+                    .target_timeout
+                    .as_ref()
+                    .map_or(0, |timeout| timeout.penalty);
+                stats.shots_hit as f32
+                    - stats.shots_missed() as f32
+                    - (expiry_penalty * stats.targets_expired as i32) as f32
+            }
+            ScoreFormula::AccuracyWeighted => kills * 100.0 * stats.accuracy(),
+            ScoreFormula::TimeBonus {
+                par_secs,
+                max_bonus,
+            } => stats
+                .kill_times
+                .iter()
+                .map(|&secs| 100.0 + max_bonus * (1.0 - secs / par_secs).max(0.0))
+                .sum(),
+            ScoreFormula::PenaltyFree => kills,
+        };
+        score + stats.bonus_points
+    }
+
+    /// Picks a position and radius for a new target.
+    /// Returns `None` if the placement is a grid without a free cell.
+    pub fn sample_target(
+        &self,
+        context: &SpawnContext,
+        occupancy: &mut GridOccupancy,
+        rng: &mut impl Rng,
+    ) -> Option<TargetSpawn> {
+        let view = context.view;
+        if let TargetPlacement::Grid {
+            center,
+            columns,
+            rows,
+            spacing,
+        } = self.target_placement
+        {
+            let cell = occupancy.claim(context.vacated_cell, rng)?;
+            let position = grid_cell_position(center, columns, rows, spacing, cell);
+            let distance = position.distance(view.translation);
+            // Neighbouring targets must not touch.
+            let radius = self.target_size.sample(distance, rng).min(spacing / 2.0);
+            return Some(TargetSpawn {
+                position,
+                radius,
+                cell: Some(cell),
+            });
+        }
+
+        let mut position = self.target_placement.sample(context, rng);
+        for _ in 1..PLACEMENT_ATTEMPTS {
+            if position.y >= MIN_TARGET_HEIGHT {
+                break;
+            }
+            position = self.target_placement.sample(context, rng);
+        }
+        let distance = position.distance(view.translation);
+        Some(TargetSpawn {
+            position,
+            radius: self.target_size.sample(distance, rng),
+            cell: None,
+        })
+    }
+}
+
+impl TargetSize {
+    /// Picks a radius for a target `distance` units away from the eye.
+    pub fn sample(&self, distance: f32, rng: &mut impl Rng) -> f32 {
+        match *self {
+            TargetSize::Radius { min, max } => sample_range(rng, min, max),
+            TargetSize::Angular { min_deg, max_deg } => {
+                angular_radius(distance, sample_range(rng, min_deg, max_deg))
+            }
+        }
+    }
+}
+
+impl TargetPlacement {
+    /// Picks a world position for a target.
+    pub fn sample(&self, context: &SpawnContext, rng: &mut impl Rng) -> Vec3 {
+        let view = context.view;
+        match *self {
+            TargetPlacement::Box { min, max } => Vec3::new(
+                sample_range(rng, min.x, max.x),
+                sample_range(rng, min.y, max.y),
+                sample_range(rng, min.z, max.z),
+            ),
+            TargetPlacement::CrosshairOffset {
+                min_deg,
+                max_deg,
+                min_distance,
+                max_distance,
+            } => {
+                let offset = sample_range(rng, min_deg, max_deg).to_radians();
+                let roll = sample_range(rng, 0.0, TAU);
+                let direction = offset_direction(view.rotation, offset, roll);
+                view.translation + direction * sample_range(rng, min_distance, max_distance)
+            }
+            TargetPlacement::ViewCone {
+                half_angle_deg,
+                min_distance,
+                max_distance,
+            } => {
+                // Uniform in cos(offset) spreads targets evenly over the cone's solid angle.
+                let min_cos = half_angle_deg.to_radians().cos();
+                let offset = sample_range(rng, min_cos, 1.0).acos();
+                let roll = sample_range(rng, 0.0, TAU);
+                let direction = offset_direction(view.rotation, offset, roll);
+                view.translation + direction * sample_range(rng, min_distance, max_distance)
+            }
+            TargetPlacement::Behind {
+                spread_deg,
+                min_distance,
+                max_distance,
+            } => {
+                let yaw = view.rotation.to_euler(EulerRot::YXZ).0;
+                let behind = Quat::from_rotation_y(yaw + PI);
+                let offset = sample_range(rng, 0.0, spread_deg).to_radians();
+                let roll = sample_range(rng, 0.0, TAU);
+                let direction = offset_direction(behind, offset, roll);
+                view.translation + direction * sample_range(rng, min_distance, max_distance)
+            }
+            TargetPlacement::FromLastKill {
+                offset_deg,
+                min_distance,
+                max_distance,
+            } => {
+                let anchor = context
+                    .last_kill
+                    .map(|position| view.looking_at(position, Vec3::Y).rotation)
+                    .unwrap_or(view.rotation);
+                let roll = sample_range(rng, 0.0, TAU);
+                let direction = offset_direction(anchor, offset_deg.to_radians(), roll);
+                view.translation + direction * sample_range(rng, min_distance, max_distance)
+            }
+            TargetPlacement::Sphere {
+                radius,
+                min_elevation_deg,
+                max_elevation_deg,
+            } => {
+                // Uniform in height spreads targets evenly over the sphere's surface.
+                let height = sample_range(
+                    rng,
+                    min_elevation_deg.to_radians().sin(),
+                    max_elevation_deg.to_radians().sin(),
+                );
+                let azimuth = sample_range(rng, 0.0, TAU);
+                let horizontal = (1.0 - height * height).sqrt();
+                let direction = Vec3::new(
+                    horizontal * azimuth.cos(),
+                    height,
+                    horizontal * azimuth.sin(),
+                );
+                view.translation + direction * radius
+            }
+            TargetPlacement::ArenaVolumes => context
+                .spawn_volumes
+                .choose_weighted(rng, SpawnVolume::volume)
+                .map_or(view.translation + view.forward() * 10.0, |volume| {
+                    volume.sample(rng)
+                }),
+            // Grid cells are claimed through `GridOccupancy` in `Scenario::sample_target`.
+            TargetPlacement::Grid { center, .. } => center,
+        }
+    }
+
+    /// Number of cells to track occupancy for, zero unless this is a grid.
+    pub fn grid_cells(&self) -> usize {
+        match *self {
+            TargetPlacement::Grid { columns, rows, .. } => columns * rows,
+            _ => 0,
+        }
+    }
+}
+
+/// Radius of a sphere `distance` away that subtends `angle_deg` degrees. The sight lines to its
+/// outline are tangent to it, so the angle is `2 * asin(radius / distance)`.
+pub fn angular_radius(distance: f32, angle_deg: f32) -> f32 {
+    distance * (angle_deg.to_radians() / 2.0).sin()
+}
+
+/// Centre of `cell`, counting row by row upwards, lowest x first.
+fn grid_cell_position(
+    center: Vec3,
+    columns: usize,
+    rows: usize,
+    spacing: f32,
+    cell: usize,
+) -> Vec3 {
+    let column = (cell % columns) as f32 - (columns - 1) as f32 / 2.0;
+    let row = (cell / columns) as f32 - (rows - 1) as f32 / 2.0;
+    center + Vec3::new(column * spacing, row * spacing, 0.0)
+}
+
+/// Direction `offset` radians away from the forward axis of `rotation`,
+/// turned by `roll` radians around it.
+fn offset_direction(rotation: Quat, offset: f32, roll: f32) -> Vec3 {
+    let local = Vec3::new(
+        offset.sin() * roll.cos(),
+        offset.sin() * roll.sin(),
+        -offset.cos(),
+    );
+    rotation * local
+}
+
+fn sample_range(rng: &mut impl Rng, min: f32, max: f32) -> f32 {
+    if max <= min {
+        return min;
+    }
+    rng.sample(Uniform::new(min, max).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn angular_radius_subtends_the_angle() {
+        for (distance, angle_deg) in [(10.0f32, 2.0f32), (2.0, 60.0), (1.0, 120.0)] {
+            let radius = angular_radius(distance, angle_deg);
+            let subtended = 2.0 * (radius / distance).asin();
+            assert!((subtended.to_degrees() - angle_deg).abs() < 1e-3);
+        }
+    }
+}
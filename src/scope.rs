@@ -0,0 +1,126 @@
+use crate::weapon::{ActiveWeapon, Weapon};
+use crate::CAMERA_FOV;
+use bevy::prelude::*;
+use bevy_fps_controller::controller::{FpsController, RenderPlayer};
+
+/// How quickly the field of view approaches its target, per second.
+const ZOOM_RATE: f32 = 20.0;
+
+pub struct ScopePlugin;
+
+impl Plugin for ScopePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_scope_overlay);
+        app.add_systems(Update, aim_down_sights);
+    }
+}
+
+/// Full-screen scope picture shown while a weapon with a scope overlay is scoped.
+#[derive(Component)]
+struct ScopeOverlay;
+
+fn spawn_scope_overlay(mut commands: Commands) {
+    commands
+        .spawn((
+            ScopeOverlay,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            Visibility::Hidden,
+        ))
+        .with_children(|parent| {
+            // A round lens, blacked out beyond its edge by a very wide outline.
+            parent
+                .spawn((
+                    Node {
+                        width: Val::Vh(90.0),
+                        height: Val::Vh(90.0),
+                        border: UiRect::all(Val::Px(3.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BorderColor(Color::BLACK),
+                    BorderRadius::MAX,
+                    Outline::new(Val::Vw(100.0), Val::ZERO, Color::BLACK),
+                ))
+                .with_children(|lens| {
+                    lens.spawn((
+                        Node {
+                            position_type: PositionType::Absolute,
+                            width: Val::Percent(100.0),
+                            height: Val::Px(1.0),
+                            ..default()
+                        },
+                        BackgroundColor(Color::BLACK),
+                    ));
+                    lens.spawn((
+                        Node {
+                            position_type: PositionType::Absolute,
+                            width: Val::Px(1.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        BackgroundColor(Color::BLACK),
+                    ));
+                });
+        });
+}
+
+fn aim_down_sights(
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut active_weapon: Query<(&mut Weapon, &mut Visibility), With<ActiveWeapon>>,
+    mut overlay: Query<&mut Visibility, (With<ScopeOverlay>, Without<Weapon>)>,
+    mut camera: Query<&mut Projection, With<RenderPlayer>>,
+    mut controllers: Query<&mut FpsController>,
+    mut unscoped_sensitivity: Local<Option<f32>>,
+    time: Res<Time>,
+) {
+    // Scoping is only possible with a drawn weapon that is not reloading.
+    let mut scope = None;
+    if let Ok((mut weapon, mut visibility)) = active_weapon.get_single_mut() {
+        if buttons.pressed(MouseButton::Right) && weapon.reload.is_none() {
+            scope = weapon.stats.scope;
+        }
+        weapon.scoped = scope.is_some();
+        let hide_view_model = scope.is_some_and(|scope| scope.overlay);
+        visibility.set_if_neq(if hide_view_model {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        });
+    }
+
+    let show_overlay = scope.is_some_and(|scope| scope.overlay);
+    for mut visibility in &mut overlay {
+        visibility.set_if_neq(if show_overlay {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+
+    let target_fov = scope.map_or(CAMERA_FOV, |scope| scope.fov);
+    for mut projection in &mut camera {
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            let blend = 1.0 - (-ZOOM_RATE * time.delta_secs()).exp();
+            perspective.fov += (target_fov - perspective.fov) * blend;
+            if (target_fov - perspective.fov).abs() < 0.001 {
+                perspective.fov = target_fov;
+            }
+        }
+    }
+
+    for mut controller in &mut controllers {
+        let base = *unscoped_sensitivity.get_or_insert(controller.sensitivity);
+        let sensitivity = base * scope.map_or(1.0, |scope| scope.sensitivity);
+        if controller.sensitivity != sensitivity {
+            controller.sensitivity = sensitivity;
+        }
+    }
+}
@@ -0,0 +1,420 @@
+use crate::arena::ArenaLayout;
+use crate::editor::GameMode;
+use crate::net::spawns_targets;
+use crate::scenario::Scenario;
+use crate::stats::{RestartSession, SessionStats};
+use crate::{spawn_target, ShotFired, TargetAge, TargetHealth, TargetRemoved};
+use bevy::prelude::*;
+use bevy::time::Stopwatch;
+use bevy::utils::HashMap;
+use bevy_fps_controller::controller::RenderPlayer;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{CallFnOptions, Dynamic, Engine, FuncArgs, Map, Scope, AST, INT};
+use std::sync::{Arc, Mutex};
+
+/// Scripts are looked up here, as by the asset server.
+const ASSET_DIRECTORY: &str = "assets";
+/// Functions a script may define, called by the game.
+const HOOKS: [&str; 4] = ["on_session_start", "on_target_killed", "on_shot", "on_tick"];
+/// Most operations a single hook call may take, so a runaway loop cannot hang the game.
+const MAX_OPERATIONS: u64 = 200_000;
+/// Most targets a script may have alive at once.
+const MAX_SCRIPT_TARGETS: usize = 64;
+/// Colour of targets spawned by scripts.
+const SCRIPT_TARGET_COLOR: Color = Color::srgb(0.9, 0.5, 0.1);
+
+/// Marks a target spawned by the script, so that the scenario's own targets are not replaced
+/// along with it.
+#[derive(Component)]
+pub struct ScriptTarget;
+
+/// Runs the scenario's Rhai script, if it has one.
+///
+/// A script defines any of these functions, each taking a `session` map with `eye` and
+/// `forward` vectors, `elapsed`, `score`, `kills` and `shots`:
+///
+/// - `on_session_start(session)` once the arena is ready.
+/// - `on_target_killed(session, kill)` with the target's `id` (-1 unless the script spawned
+///   it) and `position`.
+/// - `on_shot(session, shot)` with the shot's `origin` and `direction`.
+/// - `on_tick(session, dt)` every frame.
+///
+/// Hooks are called with `this` bound to a map that keeps its contents between calls. Scripts
+/// can call `spawn_target(position, radius)`, which returns an id, `despawn_target(id)` and
+/// `add_score(points)`, and build vectors with `vec3(x, y, z)`. They cannot import modules,
+/// evaluate code or touch anything outside the game, and their running time and memory use are
+/// capped. A script that fails is disabled for the rest of the session.
+pub struct ScriptingPlugin;
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, load_script);
+        app.add_systems(
+            Update,
+            (
+                reload_script,
+                run_script
+                    .run_if(resource_exists::<ScenarioScript>)
+                    .run_if(in_state(GameMode::Playing)),
+            )
+                .chain()
+                .run_if(spawns_targets),
+        );
+    }
+}
+
+/// Changes to the game asked for by a script, applied after its hooks return.
+#[derive(Default)]
+struct ScriptRequests {
+    next_id: INT,
+    /// Targets alive that the script spawned, to keep it under `MAX_SCRIPT_TARGETS`.
+    live_targets: usize,
+    spawns: Vec<(INT, Vec3, f32)>,
+    despawns: Vec<INT>,
+    bonus_points: f32,
+}
+
+/// A compiled scenario script and the state it keeps between hook calls.
+#[derive(Resource)]
+pub struct ScenarioScript {
+    path: String,
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    /// Bound as `this` in every hook.
+    state: Dynamic,
+    /// Hooks the script defines.
+    hooks: Vec<&'static str>,
+    requests: Arc<Mutex<ScriptRequests>>,
+    /// Targets the script spawned, by the id it was given.
+    targets: HashMap<INT, Entity>,
+    started: bool,
+    failed: bool,
+}
+
+impl ScenarioScript {
+    /// Compiles `source` and runs its top level.
+    pub fn new(path: String, source: &str) -> Result<Self, String> {
+        let requests = Arc::new(Mutex::new(ScriptRequests::default()));
+        let engine = sandboxed_engine(requests.clone());
+        let ast = engine.compile(source).map_err(|error| error.to_string())?;
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|error| error.to_string())?;
+        let hooks = HOOKS
+            .into_iter()
+            .filter(|hook| ast.iter_functions().any(|function| function.name == *hook))
+            .collect();
+        Ok(ScenarioScript {
+            path,
+            engine,
+            ast,
+            scope,
+            state: Dynamic::from_map(Map::new()),
+            hooks,
+            requests,
+            targets: HashMap::default(),
+            started: false,
+            failed: false,
+        })
+    }
+
+    /// Calls `hook` if the script defines it and has not failed.
+    fn call(&mut self, hook: &str, args: impl FuncArgs) {
+        if self.failed || !self.hooks.iter().any(|defined| *defined == hook) {
+            return;
+        }
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.state);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut self.scope,
+            &self.ast,
+            hook,
+            args,
+        );
+        if let Err(error) = result {
+            eprintln!(
+                "Script {} failed in {}, disabling it: {}",
+                self.path, hook, error
+            );
+            self.failed = true;
+        }
+    }
+
+    /// Forgets a target that left play, returning the id the script knows it by.
+    fn forget(&mut self, entity: Entity) -> Option<INT> {
+        let id = self
+            .targets
+            .iter()
+            .find_map(|(id, target)| (*target == entity).then_some(*id))?;
+        self.targets.remove(&id);
+        self.requests.lock().unwrap().live_targets -= 1;
+        Some(id)
+    }
+}
+
+/// An engine without access to files, modules or `eval`, with limits on how long scripts run
+/// and how much memory they use.
+fn sandboxed_engine(requests: Arc<Mutex<ScriptRequests>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.disable_symbol("import");
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(4096);
+    engine.set_max_array_size(1024);
+    engine.set_max_map_size(256);
+    engine.on_print(|text| println!("[script] {}", text));
+    engine.on_debug(|text, _, position| println!("[script] {:?}: {}", position, text));
+
+    engine
+        .register_type_with_name::<Vec3>("Vec3")
+        .register_fn("vec3", Vec3::new)
+        .register_get_set("x", |v: &mut Vec3| v.x, |v: &mut Vec3, x: f32| v.x = x)
+        .register_get_set("y", |v: &mut Vec3| v.y, |v: &mut Vec3, y: f32| v.y = y)
+        .register_get_set("z", |v: &mut Vec3| v.z, |v: &mut Vec3, z: f32| v.z = z)
+        .register_fn("+", |a: Vec3, b: Vec3| a + b)
+        .register_fn("-", |a: Vec3, b: Vec3| a - b)
+        .register_fn("*", |v: Vec3, scale: f32| v * scale)
+        .register_fn("length", |v: &mut Vec3| v.length())
+        .register_fn("normalize", |v: &mut Vec3| v.normalize_or_zero())
+        // Positive angles turn left, seen from above.
+        .register_fn("rotate_y", |v: &mut Vec3, degrees: f32| {
+            Quat::from_rotation_y(degrees.to_radians()) * *v
+        })
+        .register_fn("to_string", |v: &mut Vec3| {
+            format!("({:.2}, {:.2}, {:.2})", v.x, v.y, v.z)
+        });
+
+    let spawn_requests = requests.clone();
+    engine.register_fn("spawn_target", move |position: Vec3, radius: f32| -> INT {
+        let mut requests = spawn_requests.lock().unwrap();
+        if requests.live_targets >= MAX_SCRIPT_TARGETS || !position.is_finite() || radius <= 0.0 {
+            return -1;
+        }
+        let id = requests.next_id;
+        requests.next_id += 1;
+        requests.live_targets += 1;
+        requests.spawns.push((id, position, radius));
+        id
+    });
+    let despawn_requests = requests.clone();
+    engine.register_fn("despawn_target", move |id: INT| {
+        despawn_requests.lock().unwrap().despawns.push(id);
+    });
+    engine.register_fn("add_score", move |points: f32| {
+        if points.is_finite() {
+            requests.lock().unwrap().bonus_points += points;
+        }
+    });
+    engine
+}
+
+fn load_script(mut commands: Commands, scenario: Res<Scenario>) {
+    commands.remove_resource::<ScenarioScript>();
+    let Some(path) = &scenario.script else {
+        return;
+    };
+    let file = std::path::Path::new(ASSET_DIRECTORY).join(path);
+    let source = match std::fs::read_to_string(&file) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("Could not read script {}: {}", file.display(), error);
+            return;
+        }
+    };
+    match ScenarioScript::new(path.clone(), &source) {
+        Ok(script) => commands.insert_resource(script),
+        Err(error) => eprintln!("Could not load script {}: {}", path, error),
+    }
+}
+
+/// Starts the script afresh when the session restarts, picking up any change of script.
+fn reload_script(
+    commands: Commands,
+    mut restarts: EventReader<RestartSession>,
+    scenario: Res<Scenario>,
+) {
+    if restarts.read().last().is_some() {
+        load_script(commands, scenario);
+    }
+}
+
+/// The `session` argument passed to every hook.
+fn session_map(view: &Transform, stats: &SessionStats, scenario: &Scenario) -> Map {
+    let mut session = Map::new();
+    session.insert("eye".into(), Dynamic::from(view.translation));
+    session.insert("forward".into(), Dynamic::from(view.forward().as_vec3()));
+    session.insert(
+        "elapsed".into(),
+        Dynamic::from(stats.elapsed.elapsed_secs()),
+    );
+    session.insert("score".into(), Dynamic::from(scenario.score(stats)));
+    session.insert("kills".into(), Dynamic::from(stats.targets_killed as INT));
+    session.insert("shots".into(), Dynamic::from(stats.shots_fired as INT));
+    session
+}
+
+fn run_script(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut script: ResMut<ScenarioScript>,
+    mut shots_fired: EventReader<ShotFired>,
+    mut target_removed: EventReader<TargetRemoved>,
+    mut stats: ResMut<SessionStats>,
+    scenario: Res<Scenario>,
+    layout: Res<ArenaLayout>,
+    camera: Query<&Transform, With<RenderPlayer>>,
+    time: Res<Time>,
+) {
+    let script = &mut *script;
+    if !script.started {
+        if !layout.ready {
+            return;
+        }
+        script.started = true;
+        // The controller may not have positioned the camera yet, so use the spawn point.
+        let session = session_map(&layout.player_view(), &stats, &scenario);
+        script.call("on_session_start", (session,));
+    } else if let Ok(view) = camera.get_single() {
+        for removed in target_removed.read() {
+            let id = script.forget(removed.entity).unwrap_or(-1);
+            if removed.expired {
+                continue;
+            }
+            let mut kill = Map::new();
+            kill.insert("id".into(), Dynamic::from(id));
+            kill.insert("position".into(), Dynamic::from(removed.position));
+            script.call(
+                "on_target_killed",
+                (session_map(view, &stats, &scenario), kill),
+            );
+        }
+        for shot in shots_fired.read() {
+            let mut shot_map = Map::new();
+            shot_map.insert("origin".into(), Dynamic::from(shot.origin));
+            shot_map.insert("direction".into(), Dynamic::from(shot.direction));
+            script.call("on_shot", (session_map(view, &stats, &scenario), shot_map));
+        }
+        script.call(
+            "on_tick",
+            (session_map(view, &stats, &scenario), time.delta_secs()),
+        );
+    }
+
+    let requests = script.requests.clone();
+    let mut requests = requests.lock().unwrap();
+    for (id, position, radius) in requests.spawns.drain(..) {
+        let target = spawn_target(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            position,
+            radius,
+            SCRIPT_TARGET_COLOR,
+        );
+        commands.entity(target).insert((
+            ScriptTarget,
+            TargetAge {
+                stopwatch: Stopwatch::new(),
+            },
+            TargetHealth(scenario.target_health),
+        ));
+        script.targets.insert(id, target);
+    }
+    for id in requests.despawns.drain(..) {
+        if let Some(target) = script.targets.remove(&id) {
+            commands.entity(target).despawn_recursive();
+            requests.live_targets -= 1;
+        }
+    }
+    if requests.bonus_points != 0.0 {
+        stats.bonus_points += requests.bonus_points;
+        requests.bonus_points = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(source: &str) -> ScenarioScript {
+        ScenarioScript::new(String::from("test.rhai"), source).unwrap()
+    }
+
+    #[test]
+    fn runaway_hooks_are_stopped_and_disabled() {
+        let mut script = script(
+            "
+            fn on_tick() {
+                this.ticks = 1;
+                loop {}
+            }
+            ",
+        );
+        script.call("on_tick", ());
+        assert!(script.failed);
+
+        // Disabled, the hook is not run again.
+        script.state = Dynamic::from_map(Map::new());
+        script.call("on_tick", ());
+        assert!(script.state.read_lock::<Map>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn import_and_eval_do_not_compile() {
+        let engine = sandboxed_engine(Arc::default());
+        assert!(engine.compile(r#"import "file" as file;"#).is_err());
+        assert!(engine
+            .compile(r#"fn on_tick() { import "file" as file; }"#)
+            .is_err());
+        assert!(engine.compile(r#"eval("add_score(1.0)");"#).is_err());
+        assert!(ScenarioScript::new(String::from("test.rhai"), r#"eval("1")"#).is_err());
+    }
+
+    #[test]
+    fn spawns_are_capped() {
+        let mut script = script(
+            "
+            fn on_session_start() {
+                for i in 0..100 {
+                    this.last = spawn_target(vec3(0.0, 1.5, 10.0), 0.5);
+                }
+            }
+            ",
+        );
+        script.call("on_session_start", ());
+        assert!(!script.failed);
+        let requests = script.requests.lock().unwrap();
+        assert_eq!(requests.spawns.len(), MAX_SCRIPT_TARGETS);
+        assert_eq!(requests.live_targets, MAX_SCRIPT_TARGETS);
+        let last = script.state.read_lock::<Map>().unwrap()["last"].as_int();
+        assert_eq!(last, Ok(-1));
+    }
+
+    #[test]
+    fn this_keeps_its_contents_between_calls() {
+        let mut script = script(
+            "
+            fn on_tick() {
+                if this.ticks == () {
+                    this.ticks = 0;
+                }
+                this.ticks += 1;
+            }
+            ",
+        );
+        for _ in 0..3 {
+            script.call("on_tick", ());
+        }
+        assert!(!script.failed);
+        let ticks = script.state.read_lock::<Map>().unwrap()["ticks"].as_int();
+        assert_eq!(ticks, Ok(3));
+    }
+}
@@ -0,0 +1,333 @@
+use crate::history::{SessionHistory, SessionRecord};
+use crate::scenario::Scenario;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+/// Rating of a skill before any session trains it, and of a session played as well as the
+/// player's first runs of that drill.
+const BASE_RATING: f32 = 1000.0;
+/// How many runs of a drill version set the level later runs are measured against.
+const BASELINE_RUNS: usize = 3;
+/// How far a fully weighted session moves a rating towards its own performance.
+const RATING_RATE: f32 = 0.25;
+/// Sessions shown in each skill's progress chart.
+const CHART_SESSIONS: usize = 40;
+/// Height of a progress chart, in pixels.
+const CHART_HEIGHT: f32 = 40.0;
+
+/// Rates the player's skills from the session history, shown with a progress chart for each
+/// skill when K is pressed.
+///
+/// A session's performance is its score over the average of the first `BASELINE_RUNS` runs of
+/// the same drill version, times `BASE_RATING`. Each session then moves every skill's rating
+/// towards that performance, in proportion to how much the scenario trains the skill. Ratings
+/// therefore show progress since the player started each drill, comparable across drills.
+pub struct SkillsPlugin;
+
+impl Plugin for SkillsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SkillPanel>();
+        app.add_systems(Update, (toggle_skill_panel, show_skill_panel).chain());
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Skill {
+    Flicking,
+    Tracking,
+    Switching,
+    SprayControl,
+    Reaction,
+}
+
+impl Skill {
+    pub const ALL: [Skill; 5] = [
+        Skill::Flicking,
+        Skill::Tracking,
+        Skill::Switching,
+        Skill::SprayControl,
+        Skill::Reaction,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Skill::Flicking => "Flicking",
+            Skill::Tracking => "Tracking",
+            Skill::Switching => "Target switching",
+            Skill::SprayControl => "Spray control",
+            Skill::Reaction => "Reaction",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            Skill::Flicking => Color::srgb(0.9, 0.4, 0.3),
+            Skill::Tracking => Color::srgb(0.3, 0.7, 0.9),
+            Skill::Switching => Color::srgb(0.9, 0.8, 0.3),
+            Skill::SprayControl => Color::srgb(0.6, 0.4, 0.9),
+            Skill::Reaction => Color::srgb(0.4, 0.9, 0.5),
+        }
+    }
+}
+
+/// How much a scenario trains each skill, from 0 to 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SkillWeights {
+    pub flicking: f32,
+    pub tracking: f32,
+    pub switching: f32,
+    pub spray_control: f32,
+    pub reaction: f32,
+}
+
+impl SkillWeights {
+    pub fn get(&self, skill: Skill) -> f32 {
+        match skill {
+            Skill::Flicking => self.flicking,
+            Skill::Tracking => self.tracking,
+            Skill::Switching => self.switching,
+            Skill::SprayControl => self.spray_control,
+            Skill::Reaction => self.reaction,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        Skill::ALL.iter().all(|skill| self.get(*skill) <= 0.0)
+    }
+}
+
+/// Every skill's rating after one session.
+#[derive(Clone, Copy, Debug)]
+pub struct RatingSnapshot {
+    /// Seconds since the Unix epoch.
+    pub finished_at: u64,
+    pub ratings: [f32; Skill::ALL.len()],
+    /// Sessions that have trained each skill so far.
+    pub sessions: [usize; Skill::ALL.len()],
+}
+
+/// The ratings after each session that trained any skill, oldest first.
+pub fn rate_sessions(records: &[SessionRecord]) -> Vec<RatingSnapshot> {
+    let mut baselines: HashMap<(&str, &str), Vec<f32>> = HashMap::default();
+    let mut current = RatingSnapshot {
+        finished_at: 0,
+        ratings: [BASE_RATING; Skill::ALL.len()],
+        sessions: [0; Skill::ALL.len()],
+    };
+    let mut snapshots = Vec::new();
+    for record in records {
+        // Sessions saved before scenarios declared their skills fall back to the built in drill.
+        let weights = if record.skills.is_empty() {
+            Scenario::by_name(&record.scenario)
+                .map_or_else(SkillWeights::default, |scenario| scenario.skills)
+        } else {
+            record.skills
+        };
+        if weights.is_empty() {
+            continue;
+        }
+        let baseline = baselines
+            .entry((record.scenario.as_str(), record.version.as_str()))
+            .or_default();
+        if baseline.len() < BASELINE_RUNS {
+            baseline.push(record.score);
+        }
+        let reference = baseline.iter().sum::<f32>() / baseline.len() as f32;
+        if reference <= 0.0 {
+            continue;
+        }
+        let performance = BASE_RATING * record.score / reference;
+        for (index, skill) in Skill::ALL.iter().enumerate() {
+            let weight = weights.get(*skill).clamp(0.0, 1.0);
+            if weight <= 0.0 {
+                continue;
+            }
+            current.ratings[index] += RATING_RATE * weight * (performance - current.ratings[index]);
+            current.sessions[index] += 1;
+        }
+        current.finished_at = record.finished_at;
+        snapshots.push(current);
+    }
+    snapshots
+}
+
+/// Whether the skill ratings are on screen.
+#[derive(Resource, Default)]
+struct SkillPanel {
+    open: bool,
+}
+
+#[derive(Component)]
+struct SkillPanelRoot;
+
+fn toggle_skill_panel(key: Res<ButtonInput<KeyCode>>, mut panel: ResMut<SkillPanel>) {
+    if key.just_pressed(KeyCode::KeyK) {
+        panel.open = !panel.open;
+    }
+}
+
+/// Rebuilds the panel when it is opened or a session is added to the history.
+fn show_skill_panel(
+    mut commands: Commands,
+    panel: Res<SkillPanel>,
+    history: Res<SessionHistory>,
+    roots: Query<Entity, With<SkillPanelRoot>>,
+) {
+    if !panel.is_changed() && !history.is_changed() {
+        return;
+    }
+    for root in &roots {
+        commands.entity(root).despawn_recursive();
+    }
+    if !panel.open {
+        return;
+    }
+
+    let snapshots = rate_sessions(&history.records);
+    let shown = &snapshots[snapshots.len().saturating_sub(CHART_SESSIONS)..];
+    commands
+        .spawn((
+            SkillPanelRoot,
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(15.0),
+                top: Val::Px(60.0),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(15.0)),
+                row_gap: Val::Px(8.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+        ))
+        .with_children(|parent| {
+            parent.spawn(Text::new(format!(
+                "Skill ratings over the last {} sessions (K to close)",
+                shown.len()
+            )));
+            for (index, skill) in Skill::ALL.iter().enumerate() {
+                let summary = match (shown.first(), shown.last()) {
+                    (Some(first), Some(last)) if last.sessions[index] > 0 => format!(
+                        "{}: {:.0} ({:+.0})",
+                        skill.label(),
+                        last.ratings[index],
+                        last.ratings[index] - first.ratings[index]
+                    ),
+                    _ => format!("{}: unrated", skill.label()),
+                };
+                parent.spawn(Text::new(summary));
+                spawn_chart(parent, shown, index, skill.color());
+            }
+        });
+}
+
+/// A bar per session, scaled between the lowest and highest rating shown.
+fn spawn_chart(
+    parent: &mut ChildBuilder,
+    snapshots: &[RatingSnapshot],
+    index: usize,
+    color: Color,
+) {
+    let ratings: Vec<f32> = snapshots
+        .iter()
+        .filter(|snapshot| snapshot.sessions[index] > 0)
+        .map(|snapshot| snapshot.ratings[index])
+        .collect();
+    let low = ratings.iter().copied().fold(f32::INFINITY, f32::min);
+    let high = ratings.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    parent
+        .spawn(Node {
+            height: Val::Px(CHART_HEIGHT),
+            align_items: AlignItems::FlexEnd,
+            column_gap: Val::Px(2.0),
+            ..default()
+        })
+        .with_children(|chart| {
+            for rating in ratings {
+                // Flat histories are drawn at half height, and every bar stays visible.
+                let fraction = if high > low {
+                    (rating - low) / (high - low)
+                } else {
+                    0.5
+                };
+                chart.spawn((
+                    Node {
+                        width: Val::Px(6.0),
+                        height: Val::Px(2.0 + fraction * (CHART_HEIGHT - 2.0)),
+                        ..default()
+                    },
+                    BackgroundColor(color),
+                ));
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(version: &str, score: f32, skills: SkillWeights) -> SessionRecord {
+        SessionRecord {
+            scenario: String::from("custom"),
+            version: String::from(version),
+            scenario_hash: String::new(),
+            loadout_hash: String::new(),
+            seed: 0,
+            score,
+            accuracy: 1.0,
+            targets_killed: 0,
+            shots_fired: 0,
+            duration_secs: 60.0,
+            finished_at: 0,
+            difficulty_curve: Vec::new(),
+            skills,
+        }
+    }
+
+    const FLICKING_ONLY: SkillWeights = SkillWeights {
+        flicking: 1.0,
+        tracking: 0.0,
+        switching: 0.0,
+        spray_control: 0.0,
+        reaction: 0.0,
+    };
+
+    #[test]
+    fn zero_weight_leaves_a_rating_unchanged() {
+        let records: Vec<_> = [100.0, 100.0, 100.0, 200.0]
+            .into_iter()
+            .map(|score| record("a", score, FLICKING_ONLY))
+            .collect();
+        let snapshots = rate_sessions(&records);
+        assert_eq!(snapshots.len(), 4);
+
+        let last = snapshots.last().unwrap();
+        let index = |skill: Skill| Skill::ALL.iter().position(|other| *other == skill).unwrap();
+        let (flicking, tracking) = (index(Skill::Flicking), index(Skill::Tracking));
+        // Twice the baseline moves the rating a quarter of the way to twice the base rating.
+        assert_eq!(last.ratings[flicking], 1250.0);
+        assert_eq!(last.sessions[flicking], 4);
+        assert_eq!(last.ratings[tracking], BASE_RATING);
+        assert_eq!(last.sessions[tracking], 0);
+    }
+
+    #[test]
+    fn skips_sessions_without_a_positive_reference() {
+        let records = [
+            record("zero", 0.0, FLICKING_ONLY),
+            record("zero", 0.0, FLICKING_ONLY),
+            record("negative", -20.0, FLICKING_ONLY),
+            record("negative", -10.0, FLICKING_ONLY),
+        ];
+        assert!(rate_sessions(&records).is_empty());
+
+        // Other versions are measured against their own baseline.
+        let mut records = records.to_vec();
+        records.push(record("positive", 80.0, FLICKING_ONLY));
+        let snapshots = rate_sessions(&records);
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].ratings, [BASE_RATING; Skill::ALL.len()]);
+        assert_eq!(snapshots[0].sessions, [1, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,269 @@
+use bevy::prelude::*;
+use rand::distr::Uniform;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// Horizontal speed at which movement inaccuracy reaches its maximum, in metres per second.
+const FULL_SPREAD_SPEED: f32 = 9.0;
+
+/// How a weapon's shots stray from the crosshair. Spread is measured as an offset in camera space,
+/// roughly radians for small values.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpreadModel {
+    /// Spread of a shot fired after full recovery, standing still.
+    pub first_shot: f32,
+    /// Spread added by each shot.
+    pub bloom_per_shot: f32,
+    /// Most spread shooting can build up.
+    pub max_bloom: f32,
+    /// Spread recovered per second without shooting.
+    pub bloom_recovery: f32,
+    /// Steps back along the recoil pattern per second without shooting.
+    pub recoil_recovery: f32,
+    /// Spread added at full speed.
+    pub walk_spread: f32,
+    /// Horizontal speed up to which movement does not affect accuracy.
+    pub accurate_speed: f32,
+    /// Spread added to shots fired without the scope, for weapons built around one.
+    pub unscoped_inaccuracy: f32,
+    /// Multiplies the spread of shots fired in the air.
+    pub airborne_multiplier: f32,
+    /// Multiplies the spread of shots fired crouched.
+    pub crouch_multiplier: f32,
+    pub distribution: SpreadDistribution,
+}
+
+/// How shots are scattered around their aim point.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum SpreadDistribution {
+    /// Normally distributed, with the spread as two standard deviations. Most shots land near
+    /// the centre.
+    #[default]
+    Gaussian,
+    /// Uniform over a disc whose radius is the spread.
+    Cone,
+}
+
+/// Inaccuracy carried from one shot to the next.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SpreadState {
+    pub bloom: f32,
+    /// Position along the recoil pattern, in shots.
+    pub recoil: f32,
+}
+
+/// The circumstances a shot is fired in.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShotConditions {
+    pub secs_since_last_shot: f32,
+    /// Horizontal speed of the shooter.
+    pub speed: f32,
+    pub airborne: bool,
+    pub crouched: bool,
+    pub scoped: bool,
+}
+
+/// Where a shot is aimed relative to the crosshair, before random scatter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShotSpread {
+    /// Offset from the recoil pattern.
+    pub recoil: Vec3,
+    /// Size of the random scatter around the recoil offset.
+    pub spread: f32,
+    pub distribution: SpreadDistribution,
+}
+
+impl SpreadModel {
+    /// Spread added by moving at `speed`. Grows linearly from nothing at `accurate_speed` to
+    /// `walk_spread` at full speed.
+    pub fn movement_spread(&self, speed: f32) -> f32 {
+        if speed <= self.accurate_speed {
+            return 0.0;
+        }
+        let fraction = (speed - self.accurate_speed) / (FULL_SPREAD_SPEED - self.accurate_speed);
+        self.walk_spread * fraction.min(1.0)
+    }
+
+    /// The state after `secs` without shooting.
+    pub fn recover(&self, state: SpreadState, secs: f32) -> SpreadState {
+        SpreadState {
+            bloom: (state.bloom - self.bloom_recovery * secs).max(0.0),
+            recoil: (state.recoil - self.recoil_recovery * secs).max(0.0),
+        }
+    }
+
+    /// Resolves a shot fired from `state` under `conditions`, following `pattern` for recoil.
+    /// Returns the shot and the state to carry into the next one.
+    pub fn shoot(
+        &self,
+        pattern: &[Vec3],
+        state: SpreadState,
+        conditions: &ShotConditions,
+    ) -> (ShotSpread, SpreadState) {
+        let state = self.recover(state, conditions.secs_since_last_shot);
+
+        let mut spread = self.first_shot + state.bloom + self.movement_spread(conditions.speed);
+        if !conditions.scoped {
+            spread += self.unscoped_inaccuracy;
+        }
+        if conditions.airborne {
+            spread *= self.airborne_multiplier;
+        }
+        if conditions.crouched {
+            spread *= self.crouch_multiplier;
+        }
+
+        let shot = ShotSpread {
+            recoil: recoil_offset(pattern, state.recoil),
+            spread,
+            distribution: self.distribution,
+        };
+        let next = SpreadState {
+            bloom: (state.bloom + self.bloom_per_shot).min(self.max_bloom),
+            recoil: state.recoil + 1.0,
+        };
+        (shot, next)
+    }
+}
+
+impl ShotSpread {
+    /// Scatters the shot, giving its offset from the crosshair in camera space.
+    pub fn sample(&self, rng: &mut impl Rng) -> Vec3 {
+        let unit = Uniform::new(0.0f32, 1.0).unwrap();
+        let angle = rng.sample(unit) * TAU;
+        let radius = match self.distribution {
+            SpreadDistribution::Gaussian => {
+                // Box-Muller transform, using 1 - u to keep the logarithm finite.
+                let u = 1.0 - rng.sample(unit);
+                self.spread / 2.0 * (-2.0 * u.ln()).sqrt()
+            }
+            SpreadDistribution::Cone => self.spread * rng.sample(unit).sqrt(),
+        };
+        self.recoil + Vec3::new(radius * angle.cos(), radius * angle.sin(), 0.0)
+    }
+}
+
+/// Offset at a fractional position along `pattern`, holding at its last entry past the end.
+pub fn recoil_offset(pattern: &[Vec3], position: f32) -> Vec3 {
+    let Some(last) = pattern.last() else {
+        return Vec3::ZERO;
+    };
+    let index = position.floor() as usize;
+    if index + 1 >= pattern.len() {
+        return *last;
+    }
+    pattern[index].lerp(pattern[index + 1], position.fract())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+
+    fn model() -> SpreadModel {
+        SpreadModel {
+            first_shot: 0.01,
+            bloom_per_shot: 0.02,
+            max_bloom: 0.05,
+            bloom_recovery: 0.1,
+            recoil_recovery: 10.0,
+            walk_spread: 0.1,
+            accurate_speed: 3.0,
+            unscoped_inaccuracy: 0.0,
+            airborne_multiplier: 4.0,
+            crouch_multiplier: 0.5,
+            distribution: SpreadDistribution::Cone,
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn first_shot_at_rest_uses_first_shot_spread() {
+        let (shot, next) = model().shoot(&[], SpreadState::default(), &ShotConditions::default());
+        assert_close(shot.spread, 0.01);
+        assert_eq!(shot.recoil, Vec3::ZERO);
+        assert_close(next.bloom, 0.02);
+        assert_eq!(next.recoil, 1.0);
+    }
+
+    #[test]
+    fn bloom_accumulates_up_to_its_cap() {
+        let model = model();
+        let mut state = SpreadState::default();
+        let mut spreads = Vec::new();
+        for _ in 0..5 {
+            let (shot, next) = model.shoot(&[], state, &ShotConditions::default());
+            spreads.push(shot.spread);
+            state = next;
+        }
+        for (spread, expected) in spreads.iter().zip([0.01, 0.03, 0.05, 0.06, 0.06]) {
+            assert_close(*spread, expected);
+        }
+        assert_close(state.bloom, model.max_bloom);
+    }
+
+    #[test]
+    fn recovers_over_time() {
+        let model = model();
+        let state = SpreadState {
+            bloom: 0.05,
+            recoil: 8.0,
+        };
+        let partial = model.recover(state, 0.2);
+        assert_close(partial.bloom, 0.03);
+        assert_close(partial.recoil, 6.0);
+        assert_eq!(model.recover(state, 10.0), SpreadState::default());
+
+        let conditions = ShotConditions {
+            secs_since_last_shot: 1.0,
+            ..default()
+        };
+        let (shot, _) = model.shoot(&[], state, &conditions);
+        assert_close(shot.spread, model.first_shot);
+    }
+
+    #[test]
+    fn airborne_and_crouched_multiply_the_spread() {
+        let model = model();
+        let spread = |conditions: ShotConditions| {
+            model
+                .shoot(&[], SpreadState::default(), &conditions)
+                .0
+                .spread
+        };
+        let airborne = ShotConditions {
+            airborne: true,
+            ..default()
+        };
+        let crouched = ShotConditions {
+            crouched: true,
+            ..default()
+        };
+        assert_close(spread(airborne), 0.04);
+        assert_close(spread(crouched), 0.005);
+    }
+
+    #[test]
+    fn cone_samples_stay_within_the_spread() {
+        let shot = ShotSpread {
+            recoil: Vec3::new(0.02, 0.05, 0.0),
+            spread: 0.03,
+            distribution: SpreadDistribution::Cone,
+        };
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..1000 {
+            let offset = shot.sample(&mut rng) - shot.recoil;
+            assert!(offset.length() <= shot.spread + 1e-6);
+            assert_eq!(offset.z, 0.0);
+        }
+    }
+}
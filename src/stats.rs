@@ -0,0 +1,171 @@
+use crate::editor::GameMode;
+use crate::scenario::Scenario;
+use crate::Target;
+use bevy::prelude::*;
+use bevy::time::Stopwatch;
+use bevy_fps_controller::controller::{LogicalPlayer, RenderPlayer};
+use bevy_rapier3d::prelude::*;
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SessionStats::default());
+        app.add_event::<SessionFinished>();
+        app.add_event::<RestartSession>();
+        app.add_systems(
+            Update,
+            (
+                restart_stats,
+                (tick_session, track_time_on_target).run_if(in_state(GameMode::Playing)),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Sent once, when the scenario's time runs out.
+#[derive(Event)]
+pub struct SessionFinished;
+
+/// Starts the session over: stats are cleared, targets respawned and the player sent back to
+/// the spawn point.
+#[derive(Event)]
+pub struct RestartSession;
+
+/// Everything measured during the current training session.
+#[derive(Resource, Default, Debug)]
+pub struct SessionStats {
+    pub elapsed: Stopwatch,
+    pub shots_fired: u32,
+    pub shots_hit: u32,
+    pub targets_killed: u32,
+    pub targets_expired: u32,
+    pub damage_dealt: f32,
+    pub streak: u32,
+    pub best_streak: u32,
+    /// Seconds the crosshair has rested on a target.
+    pub time_on_target: f32,
+    /// How long each killed target had been alive, in seconds.
+    pub kill_times: Vec<f32>,
+    /// Points added to the score by the scenario's script.
+    pub bonus_points: f32,
+    /// The scenario's time has run out.
+    pub finished: bool,
+}
+
+impl SessionStats {
+    pub fn record_shot(&mut self, hit: bool) {
+        self.shots_fired += 1;
+        if hit {
+            self.shots_hit += 1;
+            self.streak += 1;
+            self.best_streak = self.best_streak.max(self.streak);
+        } else {
+            self.streak = 0;
+        }
+    }
+
+    pub fn record_damage(&mut self, damage: f32) {
+        self.damage_dealt += damage;
+    }
+
+    pub fn record_kill(&mut self, target_age_secs: f32) {
+        self.targets_killed += 1;
+        self.kill_times.push(target_age_secs);
+    }
+
+    pub fn record_expired(&mut self) {
+        self.targets_expired += 1;
+    }
+
+    pub fn shots_missed(&self) -> u32 {
+        self.shots_fired - self.shots_hit
+    }
+
+    /// Fraction of shots that hit a target, or 1 before the first shot.
+    pub fn accuracy(&self) -> f32 {
+        if self.shots_fired == 0 {
+            return 1.0;
+        }
+        self.shots_hit as f32 / self.shots_fired as f32
+    }
+
+    /// Digest of everything recorded this session, so that a reported result can be checked
+    /// against the stats it claims to come from.
+    pub fn checksum(&self) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new();
+        for count in [
+            self.shots_fired,
+            self.shots_hit,
+            self.targets_killed,
+            self.targets_expired,
+            self.best_streak,
+        ] {
+            hasher.update(&count.to_le_bytes());
+        }
+        hasher.update(&self.damage_dealt.to_le_bytes());
+        hasher.update(&self.time_on_target.to_le_bytes());
+        hasher.update(&self.bonus_points.to_le_bytes());
+        for kill_time in &self.kill_times {
+            hasher.update(&kill_time.to_le_bytes());
+        }
+        hasher.finalize()
+    }
+
+    pub fn kills_per_minute(&self) -> f32 {
+        let minutes = self.elapsed.elapsed_secs() / 60.0;
+        if minutes <= 0.0 {
+            return 0.0;
+        }
+        self.targets_killed as f32 / minutes
+    }
+}
+
+fn restart_stats(mut restarts: EventReader<RestartSession>, mut stats: ResMut<SessionStats>) {
+    if restarts.read().last().is_some() {
+        *stats = SessionStats::default();
+    }
+}
+
+fn tick_session(
+    mut stats: ResMut<SessionStats>,
+    mut session_finished: EventWriter<SessionFinished>,
+    scenario: Res<Scenario>,
+    time: Res<Time>,
+) {
+    stats.elapsed.tick(time.delta());
+    if !stats.finished && stats.elapsed.elapsed_secs() >= scenario.duration_secs {
+        stats.finished = true;
+        session_finished.send(SessionFinished);
+    }
+}
+
+fn track_time_on_target(
+    rapier_context: ReadRapierContext,
+    player_query: Query<Entity, With<LogicalPlayer>>,
+    camera: Query<&Transform, With<RenderPlayer>>,
+    targets: Query<(), With<Target>>,
+    mut stats: ResMut<SessionStats>,
+    time: Res<Time>,
+) {
+    let (Ok(player_handle), Ok(camera_transform)) =
+        (player_query.get_single(), camera.get_single())
+    else {
+        return;
+    };
+    let filter = QueryFilter::new()
+        .exclude_sensors()
+        .exclude_rigid_body(player_handle);
+    if let Some((entity, _)) = rapier_context.single().cast_ray(
+        camera_transform.translation,
+        camera_transform.forward().as_vec3(),
+        100.0,
+        true,
+        filter,
+    ) {
+        if targets.contains(entity) {
+            stats.time_on_target += time.delta_secs();
+        }
+    }
+}
@@ -0,0 +1,528 @@
+use crate::adaptive::{AdaptiveDifficulty, DifficultyGoal};
+use crate::arena::{ArenaCollider, ArenaSource, SpawnVolume, Wall};
+use crate::scenario::{
+    Ballistics, Scenario, ScoreFormula, TargetAudio, TargetMotion, TargetPlacement, TargetSize,
+    TargetTimeout,
+};
+use crate::spread::{SpreadDistribution, SpreadModel};
+use crate::weapon::{Loadout, WeaponScope, WeaponSlot, WeaponStats};
+use bevy::prelude::*;
+
+/// Arena and script files are looked up here, as by the asset server.
+const ASSET_DIRECTORY: &str = "assets";
+/// Printed length of a version id.
+const SHORT_ID_LENGTH: usize = 8;
+
+/// Identifies the scenario and weapon definitions a session is played with, so that results
+/// are only compared with runs of the same version of a drill.
+pub struct VersionPlugin;
+
+impl Plugin for VersionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, update_drill_version);
+    }
+}
+
+/// Hashes of the definitions in use. Kept up to date whenever they change.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DrillVersion {
+    pub scenario: blake3::Hash,
+    pub loadout: blake3::Hash,
+}
+
+impl DrillVersion {
+    pub fn new(scenario: &Scenario, loadout: &Loadout) -> Self {
+        DrillVersion {
+            scenario: definition_hash("scenario", scenario),
+            loadout: definition_hash("loadout", loadout),
+        }
+    }
+
+    /// One hash covering both definitions.
+    pub fn id(&self) -> String {
+        let mut hasher = CanonicalHasher::new("drill");
+        hasher.bytes(self.scenario.as_bytes());
+        hasher.bytes(self.loadout.as_bytes());
+        hasher.finish().to_hex().to_string()
+    }
+
+    /// The start of the id, enough to tell versions apart on screen.
+    pub fn short_id(&self) -> String {
+        self.id()[..SHORT_ID_LENGTH].to_string()
+    }
+}
+
+/// Hashes a definition in its canonical form.
+pub fn definition_hash(domain: &str, definition: &impl Canonical) -> blake3::Hash {
+    let mut hasher = CanonicalHasher::new(domain);
+    definition.canonicalize(&mut hasher);
+    hasher.finish()
+}
+
+/// A definition that can be reduced to a canonical byte sequence. Two definitions that play
+/// the same must produce the same bytes, whatever their names or presentation.
+pub trait Canonical {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher);
+}
+
+/// Feeds values to a hash in a self-delimiting encoding: strings and lists are length
+/// prefixed and enum variants are tagged, so distinct definitions cannot run together into
+/// the same bytes.
+pub struct CanonicalHasher(blake3::Hasher);
+
+impl CanonicalHasher {
+    pub fn new(domain: &str) -> Self {
+        let mut hasher = CanonicalHasher(blake3::Hasher::new());
+        hasher.str(domain);
+        hasher
+    }
+
+    pub fn finish(&self) -> blake3::Hash {
+        self.0.finalize()
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.u64(bytes.len() as u64);
+        self.0.update(bytes);
+    }
+
+    pub fn str(&mut self, value: &str) {
+        self.bytes(value.as_bytes());
+    }
+
+    /// Names an enum variant or optional part.
+    pub fn tag(&mut self, tag: &str) {
+        self.str(tag);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.0.update(&[value as u8]);
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.0.update(&value.to_le_bytes());
+    }
+
+    pub fn f32(&mut self, value: f32) {
+        // -0 equals 0, and every NaN is the same non-number.
+        let value = if value == 0.0 {
+            0.0
+        } else if value.is_nan() {
+            f32::NAN
+        } else {
+            value
+        };
+        self.0.update(&value.to_le_bytes());
+    }
+
+    pub fn vec3(&mut self, value: Vec3) {
+        self.f32(value.x);
+        self.f32(value.y);
+        self.f32(value.z);
+    }
+
+    pub fn quat(&mut self, value: Quat) {
+        // q and -q are the same rotation.
+        let value = if value.w < 0.0 { -value } else { value };
+        self.f32(value.x);
+        self.f32(value.y);
+        self.f32(value.z);
+        self.f32(value.w);
+    }
+
+    /// A file under `assets/`, by path and content.
+    pub fn asset_file(&mut self, path: &str) {
+        self.str(path);
+        let file = std::path::Path::new(ASSET_DIRECTORY).join(path);
+        match std::fs::read(&file) {
+            Ok(contents) => self.bytes(blake3::hash(&contents).as_bytes()),
+            // A missing file fails to load anyway, so its hash does not matter much.
+            Err(error) => eprintln!("Could not read {} to hash: {}", file.display(), error),
+        }
+    }
+
+    pub fn option<T>(&mut self, value: Option<&T>, canonicalize: impl FnOnce(&mut Self, &T)) {
+        match value {
+            Some(value) => {
+                self.tag("some");
+                canonicalize(self, value);
+            }
+            None => self.tag("none"),
+        }
+    }
+}
+
+/// The scenario's name and skill weights are left out, so renaming a drill or changing what it is
+/// said to train does not make it a new version.
+impl Canonical for Scenario {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        hasher.f32(self.duration_secs);
+        hasher.u64(self.target_count as u64);
+        self.target_size.canonicalize(hasher);
+        self.target_placement.canonicalize(hasher);
+        self.target_motion.canonicalize(hasher);
+        hasher.option(self.target_timeout.as_ref(), |hasher, timeout| {
+            timeout.canonicalize(hasher)
+        });
+        hasher.f32(self.target_health);
+        self.score_formula.canonicalize(hasher);
+        self.target_audio.canonicalize(hasher);
+        self.arena.canonicalize(hasher);
+        hasher.bool(self.counter_strafe_drill);
+        self.ballistics.canonicalize(hasher);
+        hasher.option(self.script.as_ref(), |hasher, path| hasher.asset_file(path));
+        hasher.option(self.adaptive.as_ref(), |hasher, adaptive| {
+            adaptive.canonicalize(hasher)
+        });
+    }
+}
+
+impl Canonical for TargetSize {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        match *self {
+            TargetSize::Radius { min, max } => {
+                hasher.tag("radius");
+                hasher.f32(min);
+                hasher.f32(max);
+            }
+            TargetSize::Angular { min_deg, max_deg } => {
+                hasher.tag("angular");
+                hasher.f32(min_deg);
+                hasher.f32(max_deg);
+            }
+        }
+    }
+}
+
+impl Canonical for TargetPlacement {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        match *self {
+            TargetPlacement::Box { min, max } => {
+                hasher.tag("box");
+                hasher.vec3(min);
+                hasher.vec3(max);
+            }
+            TargetPlacement::CrosshairOffset {
+                min_deg,
+                max_deg,
+                min_distance,
+                max_distance,
+            } => {
+                hasher.tag("crosshair_offset");
+                hasher.f32(min_deg);
+                hasher.f32(max_deg);
+                hasher.f32(min_distance);
+                hasher.f32(max_distance);
+            }
+            TargetPlacement::ViewCone {
+                half_angle_deg,
+                min_distance,
+                max_distance,
+            } => {
+                hasher.tag("view_cone");
+                hasher.f32(half_angle_deg);
+                hasher.f32(min_distance);
+                hasher.f32(max_distance);
+            }
+            TargetPlacement::Behind {
+                spread_deg,
+                min_distance,
+                max_distance,
+            } => {
+                hasher.tag("behind");
+                hasher.f32(spread_deg);
+                hasher.f32(min_distance);
+                hasher.f32(max_distance);
+            }
+            TargetPlacement::FromLastKill {
+                offset_deg,
+                min_distance,
+                max_distance,
+            } => {
+                hasher.tag("from_last_kill");
+                hasher.f32(offset_deg);
+                hasher.f32(min_distance);
+                hasher.f32(max_distance);
+            }
+            TargetPlacement::Sphere {
+                radius,
+                min_elevation_deg,
+                max_elevation_deg,
+            } => {
+                hasher.tag("sphere");
+                hasher.f32(radius);
+                hasher.f32(min_elevation_deg);
+                hasher.f32(max_elevation_deg);
+            }
+            TargetPlacement::ArenaVolumes => hasher.tag("arena_volumes"),
+            TargetPlacement::Grid {
+                center,
+                columns,
+                rows,
+                spacing,
+            } => {
+                hasher.tag("grid");
+                hasher.vec3(center);
+                hasher.u64(columns as u64);
+                hasher.u64(rows as u64);
+                hasher.f32(spacing);
+            }
+        }
+    }
+}
+
+impl Canonical for TargetMotion {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        match *self {
+            TargetMotion::Static => hasher.tag("static"),
+            TargetMotion::Strafe { speed, distance } => {
+                hasher.tag("strafe");
+                hasher.f32(speed);
+                hasher.f32(distance);
+            }
+        }
+    }
+}
+
+impl Canonical for AdaptiveDifficulty {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        match self.goal {
+            DifficultyGoal::Accuracy(goal) => {
+                hasher.tag("accuracy");
+                hasher.f32(goal);
+            }
+            DifficultyGoal::KillsPerMinute(goal) => {
+                hasher.tag("kills_per_minute");
+                hasher.f32(goal);
+            }
+        }
+        hasher.f32(self.proportional);
+        hasher.f32(self.integral);
+        hasher.f32(self.derivative);
+        hasher.f32(self.min_level);
+        hasher.f32(self.max_level);
+    }
+}
+
+impl Canonical for TargetTimeout {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        hasher.f32(self.lifetime_secs);
+        hasher.u64(self.penalty as i64 as u64);
+        hasher.bool(self.shrink);
+    }
+}
+
+impl Canonical for ScoreFormula {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        match *self {
+            ScoreFormula::Classic => hasher.tag("classic"),
+            ScoreFormula::AccuracyWeighted => hasher.tag("accuracy_weighted"),
+            ScoreFormula::TimeBonus {
+                par_secs,
+                max_bonus,
+            } => {
+                hasher.tag("time_bonus");
+                hasher.f32(par_secs);
+                hasher.f32(max_bonus);
+            }
+            ScoreFormula::PenaltyFree => hasher.tag("penalty_free"),
+        }
+    }
+}
+
+impl Canonical for TargetAudio {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        hasher.bool(self.spawn_cue);
+        hasher.bool(self.hum);
+    }
+}
+
+/// Arena files are hashed by content, since their spawn volumes shape the drill.
+impl Canonical for ArenaSource {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        match self {
+            ArenaSource::Builtin => hasher.tag("builtin"),
+            ArenaSource::Gltf { path, collider } => {
+                hasher.tag("gltf");
+                hasher.asset_file(path);
+                hasher.tag(match collider {
+                    ArenaCollider::TriMesh => "tri_mesh",
+                    ArenaCollider::ConvexDecomposition => "convex_decomposition",
+                });
+            }
+            ArenaSource::Custom {
+                walls,
+                spawn_volumes,
+            } => {
+                hasher.tag("custom");
+                hasher.u64(walls.len() as u64);
+                for wall in walls {
+                    wall.canonicalize(hasher);
+                }
+                hasher.u64(spawn_volumes.len() as u64);
+                for volume in spawn_volumes {
+                    volume.canonicalize(hasher);
+                }
+            }
+        }
+    }
+}
+
+impl Canonical for Wall {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        hasher.vec3(self.center);
+        hasher.vec3(self.half_extents);
+        hasher.quat(self.rotation);
+    }
+}
+
+impl Canonical for SpawnVolume {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        hasher.vec3(self.center);
+        hasher.vec3(self.half_extents);
+        hasher.quat(self.rotation);
+    }
+}
+
+impl Canonical for Ballistics {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        match *self {
+            Ballistics::Hitscan => hasher.tag("hitscan"),
+            Ballistics::Projectile { speed, gravity } => {
+                hasher.tag("projectile");
+                hasher.f32(speed);
+                hasher.f32(gravity);
+            }
+        }
+    }
+}
+
+impl Canonical for Loadout {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        for slot in WeaponSlot::ALL {
+            self.get(slot).canonicalize(hasher);
+        }
+    }
+}
+
+/// Only how the weapon handles is hashed. Its name, view model and sound are left out.
+impl Canonical for WeaponStats {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        hasher.f32(self.fire_interval_secs);
+        hasher.bool(self.automatic);
+        hasher.u64(self.magazine_size as u64);
+        hasher.f32(self.reload_secs);
+        hasher.f32(self.damage);
+        hasher.u64(self.spray_pattern.len() as u64);
+        for offset in &self.spray_pattern {
+            hasher.vec3(*offset);
+        }
+        self.spread.canonicalize(hasher);
+        hasher.option(self.scope.as_ref(), |hasher, scope| {
+            scope.canonicalize(hasher)
+        });
+    }
+}
+
+impl Canonical for SpreadModel {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        hasher.f32(self.first_shot);
+        hasher.f32(self.bloom_per_shot);
+        hasher.f32(self.max_bloom);
+        hasher.f32(self.bloom_recovery);
+        hasher.f32(self.recoil_recovery);
+        hasher.f32(self.walk_spread);
+        hasher.f32(self.accurate_speed);
+        hasher.f32(self.unscoped_inaccuracy);
+        hasher.f32(self.airborne_multiplier);
+        hasher.f32(self.crouch_multiplier);
+        hasher.tag(match self.distribution {
+            SpreadDistribution::Gaussian => "gaussian",
+            SpreadDistribution::Cone => "cone",
+        });
+    }
+}
+
+impl Canonical for WeaponScope {
+    fn canonicalize(&self, hasher: &mut CanonicalHasher) {
+        hasher.f32(self.fov);
+        hasher.f32(self.sensitivity);
+        hasher.bool(self.overlay);
+    }
+}
+
+fn update_drill_version(
+    mut commands: Commands,
+    scenario: Res<Scenario>,
+    loadout: Res<Loadout>,
+    version: Option<Res<DrillVersion>>,
+) {
+    if version.is_some() && !scenario.is_changed() && !loadout.is_changed() {
+        return;
+    }
+    let new_version = DrillVersion::new(&scenario, &loadout);
+    if version.is_none_or(|version| *version != new_version) {
+        println!("Drill version {}", new_version.short_id());
+        commands.insert_resource(new_version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skills::SkillWeights;
+
+    fn scenario_hash(scenario: &Scenario) -> blake3::Hash {
+        definition_hash("scenario", scenario)
+    }
+
+    fn loadout_hash(loadout: &Loadout) -> blake3::Hash {
+        definition_hash("loadout", loadout)
+    }
+
+    #[test]
+    fn scenario_hash_ignores_name_and_skills() {
+        let scenario = Scenario::gridshot();
+        let mut renamed = scenario.clone();
+        renamed.name = String::from("my gridshot");
+        renamed.skills = SkillWeights::default();
+        assert_ne!(renamed.skills, scenario.skills);
+        assert_eq!(scenario_hash(&renamed), scenario_hash(&scenario));
+    }
+
+    #[test]
+    fn scenario_hash_follows_target_size() {
+        let scenario = Scenario::gridshot();
+        let mut smaller = scenario.clone();
+        smaller.target_size = TargetSize::Radius {
+            min: 0.05,
+            max: 0.05,
+        };
+        assert_ne!(scenario_hash(&smaller), scenario_hash(&scenario));
+    }
+
+    #[test]
+    fn loadout_hash_follows_spray_offsets() {
+        let loadout = Loadout::default();
+        let mut changed = loadout.clone();
+        changed.primary.spray_pattern[3].y += 0.01;
+        assert_ne!(loadout_hash(&changed), loadout_hash(&loadout));
+    }
+
+    #[test]
+    fn negative_zero_hashes_as_zero() {
+        let hash = |value: f32| {
+            let mut hasher = CanonicalHasher::new("test");
+            hasher.f32(value);
+            hasher.finish()
+        };
+        assert_eq!(hash(-0.0), hash(0.0));
+        assert_ne!(hash(f32::MIN_POSITIVE), hash(0.0));
+
+        let loadout = Loadout::default();
+        let mut negative = loadout.clone();
+        negative.primary.spray_pattern[0] = Vec3::new(-0.0, 0.0, -0.0);
+        assert_eq!(loadout.primary.spray_pattern[0], Vec3::ZERO);
+        assert_eq!(loadout_hash(&negative), loadout_hash(&loadout));
+    }
+}
@@ -0,0 +1,433 @@
+use crate::fps_gun_plugin::{default_gun_transform, spawn_gun, GunAnimationState};
+use crate::spread::{SpreadDistribution, SpreadModel, SpreadState};
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy::time::Stopwatch;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// How long the current weapon takes to be lowered out of view.
+const HOLSTER_SECS: f32 = 0.2;
+/// How long the next weapon takes to be raised into view.
+const DRAW_SECS: f32 = 0.3;
+/// How far a holstered weapon drops below its resting position, in view model units.
+const HOLSTER_DROP: f32 = 1.2;
+/// How far a holstered weapon tilts away, in radians.
+const HOLSTER_TILT: f32 = 0.9;
+
+pub struct WeaponPlugin;
+
+impl Plugin for WeaponPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Loadout::default());
+        app.insert_resource(WeaponSwitch::default());
+        app.add_systems(Startup, spawn_loadout);
+        app.add_systems(
+            Update,
+            (tick_weapons, select_weapon, animate_weapon_switch).chain(),
+        );
+    }
+}
+
+/// A slot in the player's loadout, selected with the number keys.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WeaponSlot {
+    #[default]
+    Primary,
+    Secondary,
+    Sniper,
+}
+
+impl WeaponSlot {
+    pub const ALL: [WeaponSlot; 3] = [
+        WeaponSlot::Primary,
+        WeaponSlot::Secondary,
+        WeaponSlot::Sniper,
+    ];
+
+    pub fn index(&self) -> usize {
+        match self {
+            WeaponSlot::Primary => 0,
+            WeaponSlot::Secondary => 1,
+            WeaponSlot::Sniper => 2,
+        }
+    }
+
+    pub fn from_index(index: usize) -> Option<WeaponSlot> {
+        WeaponSlot::ALL.get(index).copied()
+    }
+
+    /// The neighbouring slot when scrolling, wrapping around at either end.
+    fn cycle(&self, step: isize) -> WeaponSlot {
+        let count = WeaponSlot::ALL.len() as isize;
+        let index = (self.index() as isize + step).rem_euclid(count);
+        WeaponSlot::ALL[index as usize]
+    }
+
+    fn key(&self) -> KeyCode {
+        match self {
+            WeaponSlot::Primary => KeyCode::Digit1,
+            WeaponSlot::Secondary => KeyCode::Digit2,
+            WeaponSlot::Sniper => KeyCode::Digit3,
+        }
+    }
+}
+
+/// How a weapon handles. One of these is defined for every loadout slot.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WeaponStats {
+    pub name: String,
+    /// glTF view model under `assets/`, which must contain Idle, Shooting and Walking animations.
+    pub model: String,
+    /// Where the view model sits in front of the view model camera.
+    pub view_transform: Transform,
+    /// Shortest time between two shots.
+    pub fire_interval_secs: f32,
+    /// Keeps firing while the button is held, rather than once per click.
+    pub automatic: bool,
+    pub magazine_size: u32,
+    pub reload_secs: f32,
+    /// Damage dealt by a single hit.
+    pub damage: f32,
+    /// Recoil offsets of successive shots in a spray, in camera space.
+    pub spray_pattern: Vec<Vec3>,
+    pub spread: SpreadModel,
+    /// Aim-down-sights behaviour on the right mouse button. `None` for weapons without one.
+    pub scope: Option<WeaponScope>,
+    pub fire_volume: f32,
+    /// Playback speed of the fire sound, before random variation.
+    pub fire_pitch: f32,
+}
+
+/// How a weapon zooms in while aiming down sights.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct WeaponScope {
+    /// Vertical field of view while scoped, in radians.
+    pub fov: f32,
+    /// Mouse sensitivity while scoped, relative to unscoped.
+    pub sensitivity: f32,
+    /// Hides the view model behind a scope overlay, rather than just zooming.
+    pub overlay: bool,
+}
+
+/// The weapons the player carries.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct Loadout {
+    pub primary: WeaponStats,
+    pub secondary: WeaponStats,
+    pub sniper: WeaponStats,
+}
+
+impl Default for Loadout {
+    fn default() -> Self {
+        Loadout {
+            primary: WeaponStats {
+                name: String::from("Rifle"),
+                model: String::from("ak47_animated.glb"),
+                view_transform: default_gun_transform(),
+                fire_interval_secs: 0.1,
+                automatic: true,
+                magazine_size: 30,
+                reload_secs: 2.5,
+                damage: 1.0,
+                spray_pattern: vec![
+                    Vec3::new(0.0, 0.0, 0.0),
+                    Vec3::new(-0.01, 0.025, 0.0),
+                    Vec3::new(-0.02, 0.05, 0.0),
+                    Vec3::new(-0.03, 0.055, 0.0),
+                    Vec3::new(-0.032, 0.065, 0.0),
+                    Vec3::new(-0.034, 0.075, 0.0),
+                    Vec3::new(-0.038, 0.08, 0.0),
+                    Vec3::new(-0.042, 0.082, 0.0),
+                    Vec3::new(-0.046, 0.085, 0.0),
+                    Vec3::new(-0.042, 0.087, 0.0),
+                    Vec3::new(-0.039, 0.090, 0.0),
+                    Vec3::new(-0.038, 0.093, 0.0),
+                ],
+                spread: SpreadModel {
+                    first_shot: 0.005,
+                    bloom_per_shot: 0.012,
+                    max_bloom: 0.065,
+                    bloom_recovery: 0.15,
+                    recoil_recovery: 10.0,
+                    walk_spread: 0.1,
+                    accurate_speed: 3.0,
+                    unscoped_inaccuracy: 0.0,
+                    airborne_multiplier: 3.0,
+                    crouch_multiplier: 0.8,
+                    distribution: SpreadDistribution::Gaussian,
+                },
+                scope: Some(WeaponScope {
+                    fov: TAU / 7.0,
+                    sensitivity: 0.8,
+                    overlay: false,
+                }),
+                fire_volume: 0.3,
+                fire_pitch: 1.1,
+            },
+            // The rifle is the only view model shipped, so the pistol is a smaller copy of it.
+            secondary: WeaponStats {
+                name: String::from("Pistol"),
+                model: String::from("ak47_animated.glb"),
+                view_transform: default_gun_transform()
+                    .with_translation(Vec3::new(0.8, -0.8, -1.3))
+                    .with_scale(Vec3::splat(0.09)),
+                fire_interval_secs: 0.15,
+                automatic: false,
+                magazine_size: 12,
+                reload_secs: 1.6,
+                damage: 0.5,
+                spray_pattern: vec![
+                    Vec3::new(0.0, 0.0, 0.0),
+                    Vec3::new(0.0, 0.02, 0.0),
+                    Vec3::new(0.005, 0.035, 0.0),
+                ],
+                spread: SpreadModel {
+                    first_shot: 0.008,
+                    bloom_per_shot: 0.015,
+                    max_bloom: 0.05,
+                    bloom_recovery: 0.12,
+                    recoil_recovery: 6.0,
+                    walk_spread: 0.05,
+                    accurate_speed: 4.0,
+                    unscoped_inaccuracy: 0.0,
+                    airborne_multiplier: 2.5,
+                    crouch_multiplier: 0.85,
+                    distribution: SpreadDistribution::Gaussian,
+                },
+                scope: None,
+                fire_volume: 0.25,
+                fire_pitch: 1.4,
+            },
+            sniper: WeaponStats {
+                name: String::from("Sniper"),
+                model: String::from("ak47_animated.glb"),
+                view_transform: default_gun_transform().with_scale(Vec3::splat(0.17)),
+                fire_interval_secs: 1.2,
+                automatic: false,
+                magazine_size: 5,
+                reload_secs: 3.2,
+                damage: 3.0,
+                spray_pattern: vec![Vec3::ZERO],
+                spread: SpreadModel {
+                    first_shot: 0.0,
+                    bloom_per_shot: 0.02,
+                    max_bloom: 0.04,
+                    bloom_recovery: 0.05,
+                    recoil_recovery: 1.0,
+                    walk_spread: 0.15,
+                    accurate_speed: 2.0,
+                    unscoped_inaccuracy: 0.08,
+                    airborne_multiplier: 4.0,
+                    crouch_multiplier: 0.7,
+                    distribution: SpreadDistribution::Cone,
+                },
+                scope: Some(WeaponScope {
+                    fov: TAU / 20.0,
+                    sensitivity: 0.35,
+                    overlay: true,
+                }),
+                fire_volume: 0.4,
+                fire_pitch: 0.75,
+            },
+        }
+    }
+}
+
+impl Loadout {
+    pub fn get(&self, slot: WeaponSlot) -> &WeaponStats {
+        match slot {
+            WeaponSlot::Primary => &self.primary,
+            WeaponSlot::Secondary => &self.secondary,
+            WeaponSlot::Sniper => &self.sniper,
+        }
+    }
+}
+
+/// State of one carried weapon, kept on its view model while other weapons are in use.
+#[derive(Component)]
+pub struct Weapon {
+    pub slot: WeaponSlot,
+    pub stats: WeaponStats,
+    pub ammo: u32,
+    /// Inaccuracy built up by recent shots.
+    pub spread: SpreadState,
+    /// Time since the last shot.
+    pub stopwatch: Stopwatch,
+    /// Running while a reload is in progress.
+    pub reload: Option<Stopwatch>,
+    /// Aiming down sights.
+    pub scoped: bool,
+}
+
+/// Marks the weapon that is drawn and ready to fire. No weapon has it mid-switch.
+#[derive(Component)]
+pub struct ActiveWeapon;
+
+/// Which weapon is in hand, and how far a switch to another one has got.
+#[derive(Resource, Default)]
+pub struct WeaponSwitch {
+    pub active: WeaponSlot,
+    /// The slot most recently asked for.
+    requested: WeaponSlot,
+    phase: SwitchPhase,
+}
+
+#[derive(Default)]
+enum SwitchPhase {
+    #[default]
+    Ready,
+    Holstering(Stopwatch),
+    Drawing(Stopwatch),
+}
+
+fn spawn_loadout(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut graphs: ResMut<Assets<AnimationGraph>>,
+    loadout: Res<Loadout>,
+    switch: Res<WeaponSwitch>,
+) {
+    for slot in WeaponSlot::ALL {
+        let stats = loadout.get(slot).clone();
+        let gun = spawn_gun(
+            &mut commands,
+            &asset_server,
+            &mut graphs,
+            &stats.model,
+            stats.view_transform,
+        );
+        let visibility = if slot == switch.active {
+            commands.entity(gun).insert(ActiveWeapon);
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        commands.entity(gun).insert((
+            Weapon {
+                slot,
+                ammo: stats.magazine_size,
+                stats,
+                spread: SpreadState::default(),
+                stopwatch: Stopwatch::new(),
+                reload: None,
+                scoped: false,
+            },
+            visibility,
+        ));
+    }
+}
+
+fn tick_weapons(mut weapons: Query<&mut Weapon>, time: Res<Time>) {
+    for mut weapon in &mut weapons {
+        weapon.stopwatch.tick(time.delta());
+    }
+}
+
+fn select_weapon(
+    key: Res<ButtonInput<KeyCode>>,
+    mut wheel: EventReader<MouseWheel>,
+    mut switch: ResMut<WeaponSwitch>,
+) {
+    let mut requested = switch.requested;
+    for slot in WeaponSlot::ALL {
+        if key.just_pressed(slot.key()) {
+            requested = slot;
+        }
+    }
+    for event in wheel.read() {
+        if event.y > 0.0 {
+            requested = requested.cycle(-1);
+        } else if event.y < 0.0 {
+            requested = requested.cycle(1);
+        }
+    }
+    if requested != switch.requested {
+        switch.requested = requested;
+    }
+}
+
+fn animate_weapon_switch(
+    mut commands: Commands,
+    mut switch: ResMut<WeaponSwitch>,
+    mut weapons: Query<(
+        Entity,
+        &mut Weapon,
+        &mut Transform,
+        &mut Visibility,
+        &mut GunAnimationState,
+    )>,
+    time: Res<Time>,
+) {
+    let switch = &mut *switch;
+    match &mut switch.phase {
+        SwitchPhase::Ready => {
+            if switch.requested == switch.active {
+                return;
+            }
+            // Put the current weapon away. An unfinished reload is abandoned.
+            for (entity, mut weapon, _, mut visibility, mut state) in &mut weapons {
+                if weapon.slot == switch.active {
+                    commands.entity(entity).remove::<ActiveWeapon>();
+                    weapon.reload = None;
+                    weapon.scoped = false;
+                    // A scope overlay may have hidden the view model.
+                    *visibility = Visibility::Inherited;
+                    state.shooting = false;
+                    state.reloading = false;
+                }
+            }
+            switch.phase = SwitchPhase::Holstering(Stopwatch::new());
+        }
+        SwitchPhase::Holstering(stopwatch) => {
+            stopwatch.tick(time.delta());
+            let progress = (stopwatch.elapsed_secs() / HOLSTER_SECS).min(1.0);
+            for (_, weapon, mut transform, _, _) in &mut weapons {
+                if weapon.slot == switch.active {
+                    *transform = holster_pose(&weapon.stats.view_transform, progress);
+                }
+            }
+            if progress < 1.0 {
+                return;
+            }
+
+            let next = switch.requested;
+            for (_, weapon, mut transform, mut visibility, _) in &mut weapons {
+                if weapon.slot == switch.active {
+                    *visibility = Visibility::Hidden;
+                } else if weapon.slot == next {
+                    *transform = holster_pose(&weapon.stats.view_transform, 1.0);
+                    *visibility = Visibility::Inherited;
+                }
+            }
+            switch.active = next;
+            switch.phase = SwitchPhase::Drawing(Stopwatch::new());
+        }
+        SwitchPhase::Drawing(stopwatch) => {
+            stopwatch.tick(time.delta());
+            let progress = (stopwatch.elapsed_secs() / DRAW_SECS).min(1.0);
+            for (entity, weapon, mut transform, _, _) in &mut weapons {
+                if weapon.slot != switch.active {
+                    continue;
+                }
+                *transform = holster_pose(&weapon.stats.view_transform, 1.0 - progress);
+                if progress >= 1.0 {
+                    commands.entity(entity).insert(ActiveWeapon);
+                }
+            }
+            if progress >= 1.0 {
+                // A switch requested mid-draw starts from here on the next frame.
+                switch.phase = SwitchPhase::Ready;
+            }
+        }
+    }
+}
+
+/// The view model lowered and tilted away by `amount`, from 0 (in hand) to 1 (out of view).
+fn holster_pose(rest: &Transform, amount: f32) -> Transform {
+    let eased = amount * amount * (3.0 - 2.0 * amount);
+    let mut pose = *rest;
+    pose.translation.y -= HOLSTER_DROP * eased;
+    pose.rotation = Quat::from_rotation_x(-HOLSTER_TILT * eased) * rest.rotation;
+    pose
+}